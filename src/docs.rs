@@ -1,11 +1,16 @@
 use self::delims::*;
+use self::diagnostic::*;
 use self::doc::*;
 use self::docfile::*;
+use self::encoding::*;
 use self::kv::*;
+use self::lexer::*;
 use self::outputs::*;
+use self::param::*;
+use self::symbol::*;
 use clap::ArgMatches;
 use dirs::home_dir;
-use glob::glob;
+use glob::{glob, Pattern};
 use handlebars::{to_json, Handlebars};
 use nom::types::CompleteStr;
 use nom::*;
@@ -13,10 +18,10 @@ use nom_locate::{position, LocatedSpan};
 use serde::{Deserialize, Serialize};
 use std::{
     borrow::Cow,
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     env,
     error::Error,
-    fs,
+    fmt, fs,
     fs::File,
     path::{Path, PathBuf},
     process::exit,
@@ -24,14 +29,34 @@ use std::{
 
 /// Given a string, convert it into a valid Path that is canonical and absolute.
 pub fn make_path(raw: Cow<str>) -> PathBuf {
-    let mut path = PathBuf::from(raw.into_owned());
+    expand_home(raw).canonicalize().unwrap()
+}
+
+/// Given a string, replace a leading `~` with the home directory, leaving the rest untouched.
+pub fn expand_home(raw: Cow<str>) -> PathBuf {
+    let path = PathBuf::from(raw.into_owned());
     if path.starts_with("~") {
-        path = home_dir().expect("Could not find home directory.").join(
+        home_dir().expect("Could not find home directory.").join(
             path.strip_prefix("~")
                 .expect("Could not remove ~ from file path."),
-        );
+        )
+    } else {
+        path
+    }
+}
+
+/// The directory to watch for a file, directory or glob pattern, which is the part of a pattern before its first `*`
+pub fn watch_root(raw: Cow<str>) -> PathBuf {
+    let path = expand_home(raw);
+    let root: PathBuf = path
+        .components()
+        .take_while(|x| !x.as_os_str().to_string_lossy().contains('*'))
+        .collect();
+    if root.as_os_str().is_empty() {
+        PathBuf::from(".").canonicalize().unwrap()
+    } else {
+        root.canonicalize().unwrap()
     }
-    path.canonicalize().unwrap()
 }
 
 /// "Main" of bashdoc
@@ -42,15 +67,40 @@ pub mod runners {
 
     /// Given the arguments received via CLI from clap, setup and run with requested delimiters, file or directory, etc.
     pub fn generate<'a>(matches: &'a ArgMatches<'a>) {
-        let delims = match matches.subcommand() {
-            ("override", Some(sub_m)) => Delimiters::override_delims(sub_m),
-            _ => Delimiters::get_delims(),
+        let config = Config::get_config();
+        let tags = config.custom_tags();
+        let forced = match matches.subcommand() {
+            ("override", Some(sub_m)) => Some(Delimiters::override_delims(sub_m)),
+            _ => match (matches.value_of("lang"), matches.value_of("profile")) {
+                (Some(lang), _) => Delimiters::preset(lang),
+                (None, Some(profile)) => Some(config.profile(Some(profile)).delims()),
+                (None, None) => None,
+            },
+        };
+        let pick = |p: &Path| Delimiters {
+            tags: &tags,
+            ..forced.unwrap_or_else(|| config.delims_for(p))
+        };
+        let encoding = match matches.value_of("encoding").map(str::parse) {
+            Some(Ok(encoding)) => encoding,
+            Some(Err(e)) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+            None => Encoding::default(),
         };
+        let strict = matches.is_present("strict");
         let all_em = start(
             Cow::Borrowed(matches.value_of("INPUT").expect("directory glob not found")),
-            delims,
+            pick,
+            encoding,
+            strict,
+            matches.is_present("hide_deprecated"),
         )
         .unwrap();
+        if strict && all_em.iter().any(|x| !x.diagnostics.is_empty()) {
+            exit(1);
+        }
         if matches.is_present("json") {
             write_json(&all_em, matches.value_of("json").unwrap());
         } else if matches.is_present("location") {
@@ -59,6 +109,12 @@ pub mod runners {
                 Option::Some(matches.value_of("location").unwrap()),
                 matches.value_of("template"),
             );
+        } else if matches.is_present("env_report") {
+            print_env_report(&all_em, matches.is_present("color"));
+        } else if matches.is_present("undocumented") {
+            for doc in &all_em {
+                print_undocumented(doc, matches.is_present("color"));
+            }
         } else {
             for doc in &all_em {
                 if matches.is_present("color") {
@@ -81,7 +137,7 @@ pub mod runners {
                 exit(1);
             }
         };
-        let path: String = make_path(Cow::Borrowed(matches.value_of("INPUT").unwrap()))
+        let path: String = watch_root(Cow::Borrowed(matches.value_of("INPUT").unwrap()))
             .to_str()
             .unwrap()
             .to_owned();
@@ -136,6 +192,9 @@ mod kv {
     /// as_kv(example) // returns [KV {key: "filename", value: "don't test me"}]
     /// ```
     pub fn as_kv(input: &str) -> Result<KV, nom::ErrorKind> {
+        if input.trim().is_empty() {
+            return Err(nom::ErrorKind::Custom(MISSING_KEY));
+        }
         let parts: Vec<_> = if input.contains(':') {
             input.split(": ").collect()
         } else {
@@ -149,291 +208,2028 @@ mod kv {
     }
 }
 
-/// Functions and declarations for Docs and parsing from strings
-mod doc {
+/// Functions and declarations for function parameters
+mod param {
     use super::*;
-    /// Represents a docstring
-    #[derive(Debug, Serialize, Deserialize, Clone, Default)]
-    pub struct Doc {
-        pub short_description: String,
-        pub long_description: String,
-        pub descriptors: Vec<KV>,
-        pub params: Vec<KV>,
-        pub returns: Vec<KV>,
-        pub position: u32,
+    /// Represents a single `@param`, such as `{int} count=5 [optional]: how many`
+    #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+    pub struct Param {
+        pub key: String,
+        pub value: String,
+        #[serde(rename = "type")]
+        pub type_name: Option<String>,
+        pub default: Option<String>,
+        pub optional: bool,
+        pub variadic: bool,
     }
 
-    impl PartialEq for Doc {
-        fn eq(&self, other: &Doc) -> bool {
-            self.short_description == other.short_description
-                && self.long_description == other.long_description
-                && self.descriptors == other.descriptors
-                && self.params == other.params
-                && self.returns == other.returns
+    impl Param {
+        /// How this parameter appears in a usage line, e.g. `<input>`, `[count=5]` or `files...`
+        pub fn usage(&self) -> String {
+            let mut name = self.key.clone();
+            if let Some(default) = &self.default {
+                name = format!("{}={}", name, default);
+            }
+            if self.variadic {
+                name.push_str("...");
+            }
+            if self.optional || self.default.is_some() {
+                format!("[{}]", name)
+            } else if self.variadic {
+                name
+            } else {
+                format!("<{}>", name)
+            }
         }
     }
 
-    /// Nom function to convert a given string in to a `Doc`
-    #[allow(clippy::cognitive_complexity)]
-    pub fn parse_doc<'a>(input: &'a str, delims: Delimiters) -> IResult<&'a str, Doc> {
-        do_parse!(
-            input,
-            short:
-                preceded!(
-                    take_until_and_consume!(delims.comm),
-                    take_until_and_consume!("\n")
-                )
-                >> long: opt!(preceded!(
-                    take_until_and_consume!(delims.comm),
-                    take_until_and_consume!("\n")
-                ))
-                >> par: opt!(many0!(complete!(map_res!(
-                    preceded!(
-                        take_until_and_consume!(delims.params),
-                        take_until_and_consume!("\n")
-                    ),
-                    as_kv
-                ))))
-                >> desc: opt!(many0!(complete!(map_res!(
-                    preceded!(
-                        take_until_and_consume!(delims.opt),
-                        take_until_and_consume!("\n")
-                    ),
-                    as_kv
-                ))))
-                >> ret: opt!(many0!(complete!(map_res!(
-                    preceded!(
-                        take_until_and_consume!(delims.ret),
-                        take_until_and_consume!("\n")
-                    ),
-                    as_kv
-                ))))
-                >> (Doc {
-                    short_description: short.to_string(),
-                    long_description: long.unwrap_or("").to_string(),
-                    descriptors: desc.unwrap_or_default(),
-                    params: par.unwrap_or_default(),
-                    returns: ret.unwrap_or_default(),
-                    position: 0
-                })
-        )
-    }
-
-    impl Doc {
-        /// Build a `Doc` from an array of strings
-        /// Parse `Doc` fields.
-        pub fn make_doc(vector: &Extracted, delims: Delimiters) -> Result<Doc, nom::ErrorKind> {
-            // println!("{:#?}", vector);
-            let parsed = parse_doc(&vector.content, delims);
-            let mut result = match parsed {
-                Ok(e) => e.1,
-                Err(_) => Default::default(),
-            };
-            result.position = vector.position.line + 1;
-            Ok(result)
+    /// Function to convert the text following a `@param` into a `Param`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let example = " {int} count=5 [optional]: how many";
+    /// as_param(example) // returns Param {key: "count", value: "how many", type_name: Some("int"), default: Some("5"), optional: true, variadic: false}
+    /// ```
+    pub fn as_param(input: &str) -> Result<Param, nom::ErrorKind> {
+        let mut result = Param::default();
+        let mut rest = input.trim();
+        if rest.starts_with('{') {
+            if let Some(end) = rest.find('}') {
+                result.type_name = Some(rest[1..end].trim().to_string());
+                rest = rest[end + 1..].trim_start();
+            }
+        }
+        let split = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let (mut name, tail) = rest.split_at(split);
+        rest = tail.trim_start();
+        if let Some(stripped) = name.strip_suffix(':') {
+            name = stripped;
+        } else {
+            if let Some(tail) = rest.strip_prefix("[optional]") {
+                result.optional = true;
+                rest = tail.trim_start();
+            }
+            if let Some(tail) = rest.strip_prefix(':') {
+                rest = tail.trim_start();
+            }
+        }
+        if let Some(index) = name.find('=') {
+            result.default = Some(name[index + 1..].to_string());
+            name = &name[..index];
+        }
+        if let Some(stripped) = name.strip_suffix("...") {
+            result.variadic = true;
+            name = stripped;
         }
+        if name.is_empty() {
+            return Err(nom::ErrorKind::Custom(MISSING_PARAM));
+        }
+        result.key = name.to_string();
+        result.value = rest.to_string();
+        Ok(result)
     }
 }
 
-/// Functions and declarations for DocFile's and parsing
-mod docfile {
+/// Functions and declarations for the definitions a docstring is attached to
+mod symbol {
     use super::*;
-    use rayon::prelude::*;
-    use std::io::prelude::*;
-    /// Represents all documentation in a file
-    #[derive(Debug, Default, Serialize, Deserialize)]
-    pub struct DocFile {
-        pub thedocs: Vec<Doc>,
-        pub filename: String,
+    /// The kind of definition found after a docstring
+    #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum SymbolKind {
+        /// `name() { ... }`
+        Function,
+        /// `function name { ... }` or `function name() { ... }`
+        FunctionKeyword,
+        /// `alias name=...`
+        Alias,
+        /// `export NAME=...`
+        Export,
+        /// `readonly NAME=...`
+        Readonly,
+        /// `declare NAME=...`
+        Declare,
+        /// `NAME=...`
+        Assignment,
     }
 
-    impl DocFile {
-        /// Append the given `Doc` to this `AllDoc`
-        #[allow(dead_code)]
-        pub fn add(&mut self, doc: Doc) {
-            self.thedocs.push(doc)
+    /// Represents a function, alias or variable definition
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    pub struct Symbol {
+        pub name: String,
+        pub kind: SymbolKind,
+        pub line: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub value: Option<String>,
+    }
+
+    impl SymbolKind {
+        /// Whether this kind of definition can be called like a command
+        pub fn is_callable(self) -> bool {
+            match self {
+                SymbolKind::Function | SymbolKind::FunctionKeyword | SymbolKind::Alias => true,
+                SymbolKind::Export
+                | SymbolKind::Readonly
+                | SymbolKind::Declare
+                | SymbolKind::Assignment => false,
+            }
+        }
+
+        /// Whether this kind of definition sets a variable
+        pub fn is_variable(self) -> bool {
+            !self.is_callable()
         }
     }
 
-    pub type Span<'a> = LocatedSpan<CompleteStr<'a>>;
-    /// Represents the string extracted from a file, including it's location in the file found.
-    pub struct Extracted<'a> {
-        pub position: Span<'a>,
-        pub content: String,
+    impl fmt::Display for SymbolKind {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let as_str = match self {
+                SymbolKind::Function | SymbolKind::FunctionKeyword => "function",
+                SymbolKind::Alias => "alias",
+                SymbolKind::Export => "export",
+                SymbolKind::Readonly => "readonly",
+                SymbolKind::Declare => "declare",
+                SymbolKind::Assignment => "assignment",
+            };
+            write!(f, "{}", as_str)
+        }
     }
 
-    /// Nom function to extract all docstring from a file.
-    pub fn parse_strings_from_file(
-        input: Span<'static>,
-        delims: Delimiters,
-    ) -> IResult<Span<'static>, Vec<Extracted<'static>>> {
-        many0!(
-            input,
-            do_parse!(
-                content:
-                    complete!(preceded!(
-                        take_until_and_consume!(delims.start),
-                        take_until_and_consume!(delims.end)
-                    ))
-                    >> pos: position!()
-                    >> (Extracted {
-                        position: pos,
-                        content: content.to_string()
-                    })
-            )
+    /// Represents a definition found while scanning a whole file
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    pub struct Definition {
+        #[serde(flatten)]
+        pub symbol: Symbol,
+        pub documented: bool,
+    }
+
+    fn is_name_char(c: char) -> bool {
+        !c.is_whitespace() && !"(){}=;|&<>\"'$`".contains(c)
+    }
+
+    fn is_var_char(c: char) -> bool {
+        c.is_alphanumeric() || c == '_'
+    }
+
+    named!(name<CompleteStr, CompleteStr>, take_while1!(is_name_char));
+
+    named!(keyword_function<CompleteStr, Symbol>,
+        do_parse!(
+            tag!("function") >> space1 >> n: name
+                >> (Symbol { name: n.to_string(), kind: SymbolKind::FunctionKeyword, line: 0, value: None })
+        )
+    );
+
+    named!(paren_function<CompleteStr, Symbol>,
+        do_parse!(
+            n: name >> space0 >> tag!("(") >> space0 >> tag!(")")
+                >> (Symbol { name: n.to_string(), kind: SymbolKind::Function, line: 0, value: None })
+        )
+    );
+
+    named!(alias<CompleteStr, Symbol>,
+        do_parse!(
+            tag!("alias") >> space1 >> n: name >> tag!("=")
+                >> (Symbol { name: n.to_string(), kind: SymbolKind::Alias, line: 0, value: None })
         )
+    );
+
+    /// Cut `raw` at the first `#` that starts a comment, outside of quotes
+    fn strip_trailing_comment(raw: &str) -> &str {
+        let mut quote = None;
+        let mut prev = ' ';
+        let mut escaped = false;
+        for (i, c) in raw.char_indices() {
+            match quote {
+                Some(q) if c == q && !escaped => quote = None,
+                Some(_) => {}
+                None if escaped => {}
+                None if c == '\'' || c == '"' => quote = Some(c),
+                None if c == '#' && prev.is_whitespace() => return &raw[..i],
+                None => {}
+            }
+            escaped = c == '\\' && !escaped && quote != Some('\'');
+            prev = c;
+        }
+        raw
     }
 
-    /// Gets all `START_DELIM->END_DELIM` comments in the zshrc
+    /// The value assigned in a definition, without the quotes around it
+    fn as_value(raw: &str) -> String {
+        let raw = strip_trailing_comment(raw).trim();
+        let quoted = raw.len() > 1
+            && (raw.starts_with('"') && raw.ends_with('"')
+                || raw.starts_with('\'') && raw.ends_with('\''));
+        if quoted {
+            raw[1..raw.len() - 1].to_string()
+        } else {
+            raw.to_string()
+        }
+    }
+
+    named!(flags<CompleteStr, Vec<CompleteStr>>,
+        many0!(terminated!(preceded!(tag!("-"), alpha1), space1))
+    );
+
+    named!(variable<CompleteStr, (CompleteStr, Option<String>)>,
+        do_parse!(
+            n: take_while1!(is_var_char) >> v: opt!(complete!(preceded!(tag!("="), rest)))
+                >> (n, v.map(|x| as_value(&x)))
+        )
+    );
+
+    named!(export<CompleteStr, Symbol>,
+        do_parse!(
+            tag!("export") >> space1 >> flags >> v: variable
+                >> (Symbol { name: v.0.to_string(), kind: SymbolKind::Export, line: 0, value: v.1 })
+        )
+    );
+
+    named!(readonly<CompleteStr, Symbol>,
+        do_parse!(
+            tag!("readonly") >> space1 >> flags >> v: variable
+                >> (Symbol { name: v.0.to_string(), kind: SymbolKind::Readonly, line: 0, value: v.1 })
+        )
+    );
+
+    named!(declare<CompleteStr, Symbol>,
+        do_parse!(
+            tag!("declare") >> space1 >> flags >> v: variable
+                >> (Symbol { name: v.0.to_string(), kind: SymbolKind::Declare, line: 0, value: v.1 })
+        )
+    );
+
+    named!(assignment<CompleteStr, Symbol>,
+        do_parse!(
+            n: take_while1!(is_var_char) >> tag!("=") >> v: rest
+                >> (Symbol { name: n.to_string(), kind: SymbolKind::Assignment, line: 0, value: Some(as_value(&v)) })
+        )
+    );
+
+    named!(definition<CompleteStr, Symbol>,
+        alt!(keyword_function | alias | export | readonly | declare | assignment | paren_function)
+    );
+
+    /// Nom function to read the definition, if any, declared on a single line
     ///
-    /// This goes through every line finding the start of the docstring
-    /// and adds every line to a `Vec` until the end delimiter.
+    /// # Example
     ///
-    /// A final `Vec` of the collected comment strings is returned.
-    pub fn get_strings_from_file<'a>(
-        p: &Path,
-        delims: Delimiters,
-    ) -> Result<Vec<Extracted<'a>>, Box<dyn Error>> {
-        let mut file = File::open(p)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-        let used = Box::leak(contents.into_boxed_str());
-        let x = parse_strings_from_file(Span::new(CompleteStr(used)), delims)?;
-        Ok(x.1)
+    /// ```
+    /// parse_symbol("alias docs='cd ~/Documents'") // returns Some(Symbol {name: "docs", kind: Alias})
+    /// ```
+    pub fn parse_symbol(line: &str) -> Option<Symbol> {
+        definition(CompleteStr(line.trim())).ok().map(|x| x.1)
     }
 
-    /// Given a `Vec<str>` make a `DocFile`
-    pub fn generate_doc_file(
-        docs: &[Extracted<'static>],
-        fname: &Path,
-        delims: Delimiters,
-    ) -> DocFile {
-        let mut all_docs: DocFile = DocFile {
-            filename: String::from(fname.file_stem().unwrap().to_str().unwrap()),
-            ..Default::default()
-        };
-        let collected: Vec<Doc> = docs
-            .par_iter()
-            .filter(|x| !x.content.is_empty())
-            .map(|x| Doc::make_doc(x, delims).unwrap())
-            .collect();
-        all_docs.thedocs = collected;
-        all_docs
+    /// Find the definition on the first non-blank line of `input`, which starts on line `first_line`
+    pub fn next_symbol(input: &str, first_line: u32) -> Option<Symbol> {
+        input
+            .lines()
+            .enumerate()
+            .find(|(_, line)| !line.trim().is_empty())
+            .and_then(|(i, line)| {
+                parse_symbol(line).map(|symbol| Symbol {
+                    line: first_line + i as u32,
+                    ..symbol
+                })
+            })
     }
 
-    fn extract_all_paths(p: Cow<str>) -> Result<Vec<PathBuf>, String> {
-        let files: Vec<_> = if p.contains('*') {
-            glob(make_path(p).to_str().unwrap())
-                .unwrap()
-                .filter_map(|x| x.ok())
-                .collect()
-        } else {
-            vec![make_path(p)]
+    /// Find the range of the definition on the first non-blank line of `input`, which starts on line `first_line`
+    ///
+    /// Functions run to their closing brace, everything else to the end of its line.
+    pub fn definition_range(input: &str, first_line: u32) -> Option<Range> {
+        let (i, line) = input
+            .lines()
+            .enumerate()
+            .find(|(_, line)| !line.trim().is_empty())?;
+        let symbol = parse_symbol(line)?;
+        let start = Point {
+            line: first_line + i as u32,
+            column: line.chars().count() - line.trim_start().chars().count() + 1,
         };
-        Ok(files)
+        let body = match symbol.kind {
+            SymbolKind::Function | SymbolKind::FunctionKeyword => {
+                closing_brace(input.lines().skip(i))
+            }
+            _ => None,
+        };
+        let end = match body {
+            Some((offset, column)) => Point {
+                line: start.line + offset as u32,
+                column,
+            },
+            None => Point {
+                line: start.line,
+                column: line.trim_end().chars().count(),
+            },
+        };
+        Some(Range { start, end })
     }
 
-    /// Given a file path and delimiters, generate a DocFile for all files requested.
-    pub fn start(p: Cow<str>, delims: Delimiters) -> Result<Vec<DocFile>, String> {
-        let x: Vec<PathBuf> = extract_all_paths(p).map_err(|e| e.to_string())?;
-        Ok(x.par_iter()
-            .map(|entry| {
-                let docs = match get_strings_from_file(entry, delims) {
-                    Ok(o) => o,
-                    Err(e) => {
-                        println!("{}", e);
-                        exit(1);
+    /// Find the brace that closes the first brace opened in `lines`, as a line offset and column
+    ///
+    /// Braces inside quotes and `#` comments are ignored.
+    fn closing_brace<'a>(lines: impl Iterator<Item = &'a str>) -> Option<(usize, usize)> {
+        let mut braces = Braces::default();
+        lines
+            .enumerate()
+            .find_map(|(i, line)| braces.read(line).map(|column| (i, column)))
+    }
+
+    /// How deeply braces are nested so far, ignoring braces inside quotes and `#` comments
+    #[derive(Default)]
+    struct Braces {
+        depth: usize,
+        quote: Option<char>,
+    }
+
+    impl Braces {
+        /// Read one more line, returning the column after the first brace that closes the outermost block
+        fn read(&mut self, line: &str) -> Option<usize> {
+            let mut closed = None;
+            let mut prev = ' ';
+            let mut escaped = false;
+            for (column, c) in line.chars().enumerate() {
+                match self.quote {
+                    Some(q) if c == q && !escaped => self.quote = None,
+                    Some(_) => {}
+                    None if escaped => {}
+                    None if c == '\'' || c == '"' => self.quote = Some(c),
+                    None if c == '#' && prev.is_whitespace() => break,
+                    None if c == '{' => self.depth += 1,
+                    None if c == '}' && self.depth > 0 => {
+                        self.depth -= 1;
+                        if self.depth == 0 && closed.is_none() {
+                            closed = Some(column + 1);
+                        }
                     }
-                };
-                generate_doc_file(&docs, entry, delims)
+                    None => {}
+                }
+                escaped = c == '\\' && !escaped && self.quote != Some('\'');
+                prev = c;
+            }
+            closed
+        }
+    }
+
+    /// The lines of `input` that begin outside of any braces, such as a function body
+    pub fn top_level(input: &str) -> Vec<&str> {
+        let mut braces = Braces::default();
+        input
+            .lines()
+            .filter(|line| {
+                let outside = braces.depth == 0 && braces.quote.is_none();
+                braces.read(line);
+                outside
             })
-            .collect())
+            .collect()
+    }
+
+    /// Find every function and alias defined in `input`, noting which of them are documented in `docs`
+    pub fn scan_definitions(input: &str, docs: &[Doc]) -> Vec<Definition> {
+        input
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| {
+                parse_symbol(line).map(|symbol| Symbol {
+                    line: i as u32 + 1,
+                    ..symbol
+                })
+            })
+            .filter(|symbol| symbol.kind.is_callable())
+            .map(|symbol| Definition {
+                documented: docs
+                    .iter()
+                    .any(|doc| doc.kind.is_some() && doc.name == symbol.name),
+                symbol,
+            })
+            .collect()
     }
 }
 
-/// Functions for presenting bashdocs to STDOUT, as JSON, or HTML
-mod outputs {
+/// Functions and declarations for classifying each line of a docstring in a single pass
+mod lexer {
     use super::*;
-    use colored::*;
-    use std::io::prelude::*;
-    /// Pretty print an `DocFile`
-    ///
-    /// Given an `AllDoc`:
-    /// ```
-    ///[
-    ///    Doc {
-    ///        short_description: "runner()",
-    ///        long_description: "This is the beginning",
-    ///        descriptors: {
-    ///            "CTRL-O": "pushs the boundaries"
-    ///        },
-    ///        params: {},
-    ///        returns: {}
-    ///    },
-    ///    Doc {
-    ///        short_description: "runner()",
-    ///        long_description: "This is the beginning",
-    ///        descriptors: {},
-    ///        params: {
-    ///            "location": "where to put it",
-    ///            "filename": "don\'t test me"
-    ///        },
-    ///        returns: {
-    ///            "nothing": ""
-    ///        }
-    ///    }
-    ///]
-    /// ```
-    /// The following will be printed to the `STDOUT` with color
-    /// ```
-    /// Help
-    /// runner: This is the beginning
-    ///     CTRL-O pushs the boundaries
-    /// runner - location, filename: This is the beginning
-    /// ```
-    pub fn printer(thedocs: &DocFile, use_color: bool) {
-        if use_color {
-            println!(
-                "{}: {}",
-                "Help".green().underline(),
-                thedocs.filename.green().underline()
-            );
-            for doc in &thedocs.thedocs {
-                let params: Vec<&str> = doc.params.iter().map(|x| x.key.as_str()).collect();
-                let as_string = params.join(", ");
-                print!("{}", doc.short_description.replace("()", "").blue().bold());
-                if doc.params.is_empty() {
-                    println!(": {}", doc.long_description);
-                } else {
-                    println!(" - {}: {}", as_string.cyan(), doc.long_description);
-                }
-                if !doc.descriptors.is_empty() {
-                    doc.descriptors
-                        .iter()
-                        .for_each(|x| println!("\t{} {}", &x.key.yellow().bold(), x.value));
-                }
+
+    /// What a single line of a docstring holds
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum LineKind {
+        Name,
+        Comment,
+        Param,
+        Descriptor,
+        Return,
+        Tag,
+        Custom,
+        Other,
+    }
+
+    /// A line of a docstring, with its delimiter or tag split from the text following it
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Token<'a> {
+        pub kind: LineKind,
+        pub tag: &'a str,
+        pub value: &'a str,
+        pub line: &'a str,
+    }
+
+    impl<'a> Token<'a> {
+        /// Whether this line holds neither text nor a tag
+        pub fn is_blank(&self) -> bool {
+            (self.kind == LineKind::Comment || self.kind == LineKind::Other)
+                && self.value.trim().is_empty()
+        }
+    }
+
+    /// Classify a single line, regardless of where it appears in the docstring
+    fn classify<'a>(line: &'a str, delims: Delimiters) -> Token<'a> {
+        let trimmed = line.trim_start();
+        let comment = strip_comment(line, delims);
+        let text = comment.unwrap_or(trimmed).trim_start();
+        let (kind, (tag, value)) = if trimmed.starts_with(delims.opt) {
+            (LineKind::Descriptor, trimmed.split_at(delims.opt.len()))
+        } else if text.starts_with(delims.params) {
+            (LineKind::Param, text.split_at(delims.params.len()))
+        } else if text.starts_with(delims.ret) {
+            (LineKind::Return, text.split_at(delims.ret.len()))
+        } else if text.starts_with('@') {
+            let split = text.find(char::is_whitespace).unwrap_or(text.len());
+            let (tag, value) = text.split_at(split);
+            if delims.tags.iter().any(|x| x.name == tag[1..]) {
+                (LineKind::Custom, (tag, value))
+            } else {
+                (LineKind::Tag, (tag, value))
             }
+        } else if let Some(text) = comment {
+            (LineKind::Comment, ("", text))
         } else {
-            println!("Help: {}", thedocs.filename);
-            for doc in &thedocs.thedocs {
-                let params: Vec<&str> = doc.params.iter().map(|x| x.key.as_str()).collect();
-                let as_string = params.join(", ");
-                print!("{}", doc.short_description.replace("()", ""));
-                if doc.params.is_empty() {
-                    println!(": {}", doc.long_description);
-                } else {
-                    println!(" - {}: {}", as_string, doc.long_description);
-                }
-                if !doc.descriptors.is_empty() {
-                    doc.descriptors
-                        .iter()
-                        .for_each(|x| println!("\t{} {}", &x.key, x.value));
-                }
-            }
+            (LineKind::Other, ("", trimmed))
+        };
+        Token {
+            kind,
+            tag,
+            value,
+            line,
         }
     }
 
-    /// Given a list of `DocFile` and a file path, write the JSON representation to a file.
+    /// Classify every line of a docstring, taking the first line with any text as its name
+    pub fn lex<'a>(input: &'a str, delims: Delimiters) -> Vec<Token<'a>> {
+        let mut named = false;
+        input
+            .lines()
+            .map(|line| {
+                let mut token = classify(line, delims);
+                if !named && !token.is_blank() {
+                    named = true;
+                    if token.kind == LineKind::Comment {
+                        token.kind = LineKind::Name;
+                    }
+                }
+                token
+            })
+            .collect()
+    }
+}
+
+/// Functions and declarations for Docs and parsing from strings
+mod doc {
+    use super::*;
+    /// Represents a docstring
+    #[derive(Debug, Serialize, Deserialize, Clone, Default)]
+    pub struct Doc {
+        pub name: String,
+        pub kind: Option<SymbolKind>,
+        pub value: Option<String>,
+        pub short_description: String,
+        pub long_description: String,
+        pub descriptors: Vec<KV>,
+        pub params: Vec<Param>,
+        pub returns: Vec<KV>,
+        pub examples: Vec<Example>,
+        pub deprecated: Option<Deprecation>,
+        pub see: Vec<Reference>,
+        pub env: Vec<KV>,
+        pub exit_codes: Vec<ExitCode>,
+        pub stdout: Vec<String>,
+        pub stderr: Vec<String>,
+        pub tags: BTreeMap<String, TagValue>,
+        pub section: Option<String>,
+        pub position: u32,
+        pub block_range: Range,
+        pub definition_range: Option<Range>,
+    }
+
+    /// Represents the documentation of a file itself, from a docstring tagged `@file` or a leading comment
+    #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+    pub struct FileHeader {
+        pub summary: String,
+        pub description: String,
+        pub author: Option<String>,
+        pub license: Option<String>,
+    }
+
+    /// A line and column in a file, both counted from 1
+    #[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+    pub struct Point {
+        pub line: u32,
+        pub column: usize,
+    }
+
+    /// The first and last characters of a docstring or definition
+    #[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+    pub struct Range {
+        pub start: Point,
+        pub end: Point,
+    }
+
+    /// The value of a user-defined tag declared in `.bashdocrc`
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    #[serde(untagged)]
+    pub enum TagValue {
+        Single(String),
+        List(Vec<String>),
+    }
+
+    impl fmt::Display for TagValue {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                TagValue::Single(value) => write!(f, "{}", value),
+                TagValue::List(values) => write!(f, "{}", values.join(", ")),
+            }
+        }
+    }
+
+    /// Represents an `@see name` cross-reference, resolved to where `name` is documented
+    #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+    pub struct Reference {
+        pub name: String,
+        pub file: Option<String>,
+        pub path: Option<String>,
+        pub line: Option<u32>,
+        /// The line of the `@see` itself
+        #[serde(skip)]
+        pub source_line: u32,
+    }
+
+    /// Function to convert the text following a `@see` into one `Reference` per name
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// as_references(" fs(), fbr") // returns [Reference {name: "fs", ..}, Reference {name: "fbr", ..}]
+    /// ```
+    pub fn as_references(input: &str) -> Vec<Reference> {
+        input
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .map(|x| x.trim_end_matches("()"))
+            .filter(|x| !x.is_empty())
+            .map(|x| Reference {
+                name: x.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// Represents an `@example` block, with its code kept verbatim
+    #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+    pub struct Example {
+        pub description: String,
+        pub code: String,
+        pub output: Option<String>,
+    }
+
+    /// Represents an `@deprecated [since] [use other_fn]` tag
+    #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+    pub struct Deprecation {
+        pub since: Option<String>,
+        pub replacement: Option<String>,
+    }
+
+    impl fmt::Display for Deprecation {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "deprecated")?;
+            if let Some(since) = &self.since {
+                write!(f, " since {}", since)?;
+            }
+            if let Some(replacement) = &self.replacement {
+                write!(f, ", use {} instead", replacement)?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Function to convert the text following a `@deprecated` into a `Deprecation`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// as_deprecation(" 0.5.0 use fs") // returns Deprecation {since: Some("0.5.0"), replacement: Some("fs")}
+    /// ```
+    pub fn as_deprecation(input: &str) -> Deprecation {
+        let mut words = input.split_whitespace().peekable();
+        let since = match words.peek() {
+            Some(&"use") | None => None,
+            Some(_) => words.next().map(str::to_string),
+        };
+        let replacement = match words.next() {
+            Some("use") => words.next().map(str::to_string),
+            _ => None,
+        };
+        Deprecation { since, replacement }
+    }
+
+    /// Represents an `@exitcode N: meaning` tag
+    #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+    pub struct ExitCode {
+        pub code: u8,
+        pub meaning: String,
+    }
+
+    /// Function to convert the text following a `@exitcode` into an `ExitCode`, requiring a status from 0 to 255
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// as_exitcode(" 1: no match found") // returns ExitCode {code: 1, meaning: "no match found"}
+    /// ```
+    pub fn as_exitcode(input: &str) -> Result<ExitCode, nom::ErrorKind> {
+        let kv = as_kv(input.trim_start()).map_err(|_| nom::ErrorKind::Digit)?;
+        let code = kv
+            .key
+            .trim_end_matches(':')
+            .parse()
+            .map_err(|_| nom::ErrorKind::Digit)?;
+        Ok(ExitCode {
+            code,
+            meaning: kv.value,
+        })
+    }
+
+    const EXAMPLE_TAG: &str = "@example";
+    const DEPRECATED_TAG: &str = "@deprecated";
+    const SEE_TAG: &str = "@see";
+    const ENV_TAG: &str = "@env";
+    const EXITCODE_TAG: &str = "@exitcode";
+    const STDOUT_TAG: &str = "@stdout";
+    const STDERR_TAG: &str = "@stderr";
+    const FILE_TAG: &str = "@file";
+    const AUTHOR_TAG: &str = "@author";
+    const LICENSE_TAG: &str = "@license";
+    const OUTPUT_MARKER: &str = "=>";
+    /// Every built-in tag other than `@param` and `@return`
+    pub const KNOWN_TAGS: &[&str] = &[
+        EXAMPLE_TAG,
+        DEPRECATED_TAG,
+        SEE_TAG,
+        ENV_TAG,
+        EXITCODE_TAG,
+        STDOUT_TAG,
+        STDERR_TAG,
+        FILE_TAG,
+        AUTHOR_TAG,
+        LICENSE_TAG,
+    ];
+
+    /// Whether a line ends an `@example`, which keeps every other comment line verbatim
+    fn ends_example(token: &Token) -> bool {
+        match token.kind {
+            LineKind::Param | LineKind::Return | LineKind::Custom | LineKind::Other => true,
+            LineKind::Tag => KNOWN_TAGS.contains(&token.tag),
+            LineKind::Name | LineKind::Comment | LineKind::Descriptor => false,
+        }
+    }
+
+    impl PartialEq for Doc {
+        fn eq(&self, other: &Doc) -> bool {
+            self.name == other.name
+                && self.kind == other.kind
+                && self.short_description == other.short_description
+                && self.long_description == other.long_description
+                && self.descriptors == other.descriptors
+                && self.params == other.params
+                && self.returns == other.returns
+                && self.examples == other.examples
+                && self.deprecated == other.deprecated
+                && self.see == other.see
+                && self.env == other.env
+                && self.exit_codes == other.exit_codes
+                && self.stdout == other.stdout
+                && self.stderr == other.stderr
+                && self.tags == other.tags
+        }
+    }
+
+    /// Strip the comment delimiter from a line, treating a bare delimiter as an empty line
+    pub fn strip_comment<'a>(line: &'a str, delims: Delimiters) -> Option<&'a str> {
+        let trimmed = line.trim_start();
+        if let Some(text) = trimmed.strip_prefix(delims.comm) {
+            Some(text)
+        } else if trimmed.trim_end() == delims.comm.trim() {
+            Some("")
+        } else {
+            None
+        }
+    }
+
+    /// Join lines kept verbatim, dropping blank lines at either end
+    fn join_verbatim(lines: &[&str]) -> String {
+        lines.join("\n").trim_matches('\n').to_string()
+    }
+
+    /// Build an `Example` from the text after an `@example` tag and the comment lines following it
+    ///
+    /// Lines after a `# =>` marker are the expected output of the example.
+    fn as_example(description: &str, lines: &[&str]) -> Example {
+        let mut code = Vec::new();
+        let mut output: Option<Vec<&str>> = None;
+        for text in lines {
+            if let Some(out) = text.trim_start().strip_prefix(OUTPUT_MARKER) {
+                let lines = output.get_or_insert_with(Vec::new);
+                if !out.trim().is_empty() {
+                    lines.push(out.trim());
+                }
+            } else if let Some(lines) = output.as_mut() {
+                lines.push(text);
+            } else {
+                code.push(*text);
+            }
+        }
+        Example {
+            description: description.trim().to_string(),
+            code: join_verbatim(&code),
+            output: output.map(|x| join_verbatim(&x)),
+        }
+    }
+
+    /// A single tagged line of a docstring
+    enum Tag {
+        Param(Param),
+        Descriptor(KV),
+        Return(KV),
+        Example(Example),
+        Deprecated(Deprecation),
+        See(Vec<Reference>),
+        Env(KV),
+        ExitCode(ExitCode),
+        Stdout(String),
+        Stderr(String),
+        Custom(CustomTag, String),
+        Other,
+    }
+
+    impl Doc {
+        /// Add a parsed `Tag` to the field of this `Doc` that holds it
+        fn add_tag(&mut self, tag: Tag) {
+            match tag {
+                Tag::Param(param) => self.params.push(param),
+                Tag::Descriptor(kv) => self.descriptors.push(kv),
+                Tag::Return(kv) => self.returns.push(kv),
+                Tag::Example(example) => self.examples.push(example),
+                Tag::Deprecated(deprecation) => {
+                    self.deprecated.get_or_insert(deprecation);
+                }
+                Tag::See(references) => self.see.extend(references),
+                Tag::Env(kv) => self.env.push(kv),
+                Tag::ExitCode(code) => self.exit_codes.push(code),
+                Tag::Stdout(text) => self.stdout.push(text),
+                Tag::Stderr(text) => self.stderr.push(text),
+                Tag::Custom(tag, value) => self.add_custom(tag, &value),
+                Tag::Other => {}
+            }
+        }
+
+        /// Add a user-defined tag, keeping the first value of single tags and every value of lists
+        fn add_custom(&mut self, tag: CustomTag, value: &str) {
+            match tag.kind {
+                TagKind::Single => {
+                    self.tags
+                        .entry(tag.name)
+                        .or_insert_with(|| TagValue::Single(value.to_string()));
+                }
+                TagKind::List => {
+                    let entry = self
+                        .tags
+                        .entry(tag.name)
+                        .or_insert_with(|| TagValue::List(Vec::new()));
+                    if let TagValue::List(values) = entry {
+                        values.extend(
+                            value
+                                .split(',')
+                                .map(str::trim)
+                                .filter(|x| !x.is_empty())
+                                .map(str::to_string),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Convert a tagged `Token` into a `Tag`, failing on values that cannot be parsed
+    fn as_tag(token: &Token, delims: Delimiters) -> Result<Tag, nom::ErrorKind> {
+        let value = token.value;
+        match token.kind {
+            LineKind::Descriptor => as_kv(value).map(Tag::Descriptor),
+            LineKind::Param => as_param(value).map(Tag::Param),
+            LineKind::Return => as_kv(value).map(Tag::Return),
+            LineKind::Custom => match delims.tags.iter().find(|x| x.name == token.tag[1..]) {
+                Some(tag) => Ok(Tag::Custom(tag.clone(), value.trim().to_string())),
+                None => Ok(Tag::Other),
+            },
+            LineKind::Tag => match token.tag {
+                DEPRECATED_TAG => Ok(Tag::Deprecated(as_deprecation(value))),
+                SEE_TAG => Ok(Tag::See(as_references(value))),
+                ENV_TAG => as_kv(value.trim_start().trim_start_matches('$')).map(Tag::Env),
+                EXITCODE_TAG => as_exitcode(value).map(Tag::ExitCode),
+                STDOUT_TAG => Ok(Tag::Stdout(value.trim().to_string())),
+                STDERR_TAG => Ok(Tag::Stderr(value.trim().to_string())),
+                _ => Ok(Tag::Other),
+            },
+            _ => Ok(Tag::Other),
+        }
+    }
+
+    /// Join description lines into paragraphs, split wherever a blank comment line appears
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// join_paragraphs(&["moves to", "a directory", "", "see cd(1)"]) // returns "moves to a directory\n\nsee cd(1)"
+    /// ```
+    pub fn join_paragraphs(lines: &[&str]) -> String {
+        lines
+            .split(|x| x.trim().is_empty())
+            .filter(|x| !x.is_empty())
+            .map(|x| x.iter().map(|l| l.trim()).collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// Function to convert a given string in to a `Doc`, reading the `Token` of each line once
+    pub fn parse_doc<'a>(input: &'a str, delims: Delimiters) -> IResult<&'a str, Doc> {
+        let lexed = lex(input, delims);
+        let mut tokens = lexed.iter().skip_while(|x| x.is_blank()).peekable();
+        let short = match tokens.next() {
+            Some(token) if token.kind == LineKind::Name => token.value.trim(),
+            Some(token) => {
+                let at = token.line.trim_start();
+                return Err(Err::Failure(error_position!(
+                    at,
+                    ErrorKind::Custom(MISSING_NAME)
+                )));
+            }
+            None => {
+                return Err(Err::Failure(error_position!(
+                    input,
+                    ErrorKind::Custom(MISSING_NAME)
+                )))
+            }
+        };
+        let mut long = Vec::new();
+        while let Some(token) = tokens.next_if(|x| x.kind == LineKind::Comment) {
+            long.push(token.value);
+        }
+        let mut doc = Doc {
+            name: short.replace("()", "").trim().to_string(),
+            short_description: short.to_string(),
+            long_description: join_paragraphs(&long),
+            ..Default::default()
+        };
+        while let Some(token) = tokens.next() {
+            if token.kind == LineKind::Tag && token.tag == EXAMPLE_TAG {
+                let mut lines = Vec::new();
+                while let Some(line) = tokens.next_if(|x| !ends_example(x)) {
+                    lines.push(strip_comment(line.line, delims).unwrap_or(line.value));
+                }
+                doc.add_tag(Tag::Example(as_example(token.value, &lines)));
+            } else {
+                match as_tag(token, delims) {
+                    Ok(Tag::See(references)) => {
+                        let source_line = input[..input.offset(token.line)].matches('\n').count();
+                        doc.add_tag(Tag::See(
+                            references
+                                .into_iter()
+                                .map(|x| Reference {
+                                    source_line: source_line as u32,
+                                    ..x
+                                })
+                                .collect(),
+                        ))
+                    }
+                    Ok(tag) => doc.add_tag(tag),
+                    Err(e) => {
+                        return Err(Err::Failure(error_position!(token.value.trim_start(), e)))
+                    }
+                }
+            }
+        }
+        Ok((&input[input.len()..], doc))
+    }
+
+    /// Whether a docstring documents its file rather than a function
+    pub fn is_file_block(input: &str, delims: Delimiters) -> bool {
+        lex(input, delims)
+            .iter()
+            .any(|x| x.kind == LineKind::Tag && x.tag == FILE_TAG)
+    }
+
+    /// Build a `FileHeader` from a docstring tagged `@file` or a leading comment
+    ///
+    /// The `@file` value is the summary. Without one, the first paragraph of the description is used.
+    pub fn as_header(input: &str, delims: Delimiters) -> FileHeader {
+        let mut header = FileHeader::default();
+        let mut authors = Vec::new();
+        let mut lines = Vec::new();
+        for token in lex(input, delims) {
+            match (token.kind, token.tag) {
+                (LineKind::Tag, FILE_TAG) => header.summary = token.value.trim().to_string(),
+                (LineKind::Tag, AUTHOR_TAG) => authors.push(token.value.trim()),
+                (LineKind::Tag, LICENSE_TAG) => {
+                    header.license = Some(token.value.trim().to_string())
+                }
+                (LineKind::Name, _) | (LineKind::Comment, _) => lines.push(token.value),
+                _ => {}
+            }
+        }
+        let description = join_paragraphs(&lines);
+        if header.summary.is_empty() {
+            let mut paragraphs = description.splitn(2, "\n\n");
+            header.summary = paragraphs.next().unwrap_or_default().to_string();
+            header.description = paragraphs.next().unwrap_or_default().to_string();
+        } else {
+            header.description = description;
+        }
+        if !authors.is_empty() {
+            header.author = Some(authors.join(", "));
+        }
+        header
+    }
+
+    impl FileHeader {
+        /// The author and license, each paired with its label
+        pub fn details(&self) -> Vec<(&'static str, &str)> {
+            let author = self.author.iter().map(|x| ("Author", x.as_str()));
+            let license = self.license.iter().map(|x| ("License", x.as_str()));
+            author.chain(license).collect()
+        }
+    }
+
+    impl Doc {
+        /// A usage line built from the name and params, e.g. `mp <input> [count=5] files...`
+        pub fn usage(&self) -> String {
+            let mut parts = vec![self.name.clone()];
+            parts.extend(self.params.iter().map(Param::usage));
+            parts.join(" ")
+        }
+
+        /// The line this is defined on, or where its docstring starts when nothing is defined below it
+        pub fn line(&self) -> u32 {
+            self.definition_range
+                .map_or(self.block_range.start.line, |x| x.start.line)
+        }
+
+        /// Whether this documents a variable rather than a function or alias
+        pub fn is_variable(&self) -> bool {
+            self.kind.is_some_and(SymbolKind::is_variable)
+        }
+
+        /// The definition of a variable as it might be written, e.g. `export EDITOR=vim`
+        pub fn declaration(&self) -> String {
+            let mut result = match self.kind {
+                Some(SymbolKind::Assignment) | None => self.name.clone(),
+                Some(kind) => format!("{} {}", kind, self.name),
+            };
+            if let Some(value) = &self.value {
+                result.push('=');
+                result.push_str(value);
+            }
+            result
+        }
+
+        /// Every `@stdout` and `@stderr` paired with the name of its stream
+        pub fn outputs(&self) -> Vec<(&'static str, &str)> {
+            let stdout = self.stdout.iter().map(|x| ("stdout", x.as_str()));
+            let stderr = self.stderr.iter().map(|x| ("stderr", x.as_str()));
+            stdout.chain(stderr).collect()
+        }
+
+        /// Build a `Doc` from an array of strings
+        /// Parse `Doc` fields, describing where parsing failed if it does.
+        pub fn make_doc(vector: &Extracted, delims: Delimiters) -> Result<Doc, Diagnostic> {
+            let content = &vector.content;
+            let first_line = vector.block.start.line;
+            let parsed = parse_doc(content, delims);
+            let mut result = match parsed {
+                Ok(e) => e.1,
+                Err(Err::Error(Context::Code(at, kind)))
+                | Err(Err::Failure(Context::Code(at, kind))) => {
+                    return Err(Diagnostic::in_block(
+                        &vector.prefix,
+                        content,
+                        at,
+                        first_line,
+                        &kind,
+                    ))
+                }
+                Err(_) => {
+                    let kind = ErrorKind::Custom(MISSING_NAME);
+                    return Err(Diagnostic::in_block(
+                        &vector.prefix,
+                        content,
+                        content,
+                        first_line,
+                        &kind,
+                    ));
+                }
+            };
+            if let Some(symbol) = &vector.definition {
+                result.name = symbol.name.clone();
+                result.kind = Some(symbol.kind);
+                result.value = symbol.value.clone();
+            }
+            result.position = vector.block.end.line + 1;
+            result.block_range = vector.block;
+            for reference in &mut result.see {
+                reference.source_line += first_line;
+            }
+            result.section = vector.section.clone();
+            result.definition_range = vector.definition_range;
+            Ok(result)
+        }
+    }
+}
+
+/// Functions and declarations for reporting docstrings that could not be parsed
+mod diagnostic {
+    use super::*;
+
+    pub const MISSING_NAME: u32 = 1;
+    pub const MISSING_KEY: u32 = 2;
+    pub const MISSING_PARAM: u32 = 3;
+    pub const UNTERMINATED: u32 = 4;
+    pub const EMPTY_BLOCK: u32 = 5;
+    pub const STRAY_TAG: u32 = 6;
+
+    /// Represents a parse failure and where in its file it happened
+    #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+    pub struct Diagnostic {
+        pub file: String,
+        pub line: u32,
+        pub column: usize,
+        pub expected: String,
+        pub snippet: String,
+        pub width: usize,
+    }
+
+    /// Describe what the parser expected when it failed with `kind`
+    pub fn expected(kind: &ErrorKind) -> &'static str {
+        match kind {
+            ErrorKind::Custom(MISSING_NAME) => "a name line after the start delimiter",
+            ErrorKind::Custom(MISSING_KEY) => "a name after the tag",
+            ErrorKind::Custom(MISSING_PARAM) => "a parameter name after the tag",
+            ErrorKind::Custom(UNTERMINATED) => "an end delimiter to close this docstring",
+            ErrorKind::Custom(EMPTY_BLOCK) => "a name line inside this docstring",
+            ErrorKind::Custom(STRAY_TAG) => "this tag to be inside a docstring",
+            ErrorKind::Digit => "an exit status from 0 to 255",
+            _ => "a well-formed docstring",
+        }
+    }
+
+    impl Diagnostic {
+        /// Build a `Diagnostic` pointing at `at`, a slice of `content`, where `content` begins on line `first_line`
+        pub fn new(content: &str, at: &str, first_line: u32, kind: &ErrorKind) -> Diagnostic {
+            Diagnostic::at_offset(content, content.offset(at), first_line, kind)
+        }
+
+        /// Build a `Diagnostic` pointing at `at`, a slice of a docstring's `content`
+        ///
+        /// `prefix` is the text on the docstring's first line before `content`, so that the snippet and column match the file.
+        pub fn in_block(
+            prefix: &str,
+            content: &str,
+            at: &str,
+            first_line: u32,
+            kind: &ErrorKind,
+        ) -> Diagnostic {
+            let offset = prefix.len() + content.offset(at);
+            Diagnostic::at_offset(&format!("{}{}", prefix, content), offset, first_line, kind)
+        }
+
+        fn at_offset(
+            content: &str,
+            offset: usize,
+            first_line: u32,
+            kind: &ErrorKind,
+        ) -> Diagnostic {
+            let line_start = content[..offset].rfind('\n').map_or(0, |x| x + 1);
+            let line_end = content[offset..]
+                .find('\n')
+                .map_or(content.len(), |x| offset + x);
+            let word = content[offset..line_end]
+                .find(|c: char| c.is_whitespace() || c == ':')
+                .unwrap_or(line_end - offset);
+            Diagnostic {
+                file: String::new(),
+                line: first_line + content[..line_start].matches('\n').count() as u32,
+                column: content[line_start..offset].chars().count() + 1,
+                expected: expected(kind).to_string(),
+                snippet: content[line_start..line_end]
+                    .trim_end_matches('\r')
+                    .to_string(),
+                width: content[offset..offset + word].chars().count().max(1),
+            }
+        }
+    }
+
+    /// Find unterminated and empty docstrings, and tags written outside of any docstring
+    pub fn lint(content: &str, delims: Delimiters) -> Vec<Diagnostic> {
+        let mut found = Vec::new();
+        let begin = leading_comment(content, delims)
+            .map_or(skip_modeline(content), |x| content.offset(x) + x.len());
+        let mut rest = &content[begin..];
+        loop {
+            let open = match rest.find(delims.start) {
+                Some(open) => open,
+                None => {
+                    stray_tags(content, rest, delims, &mut found);
+                    return found;
+                }
+            };
+            stray_tags(content, &rest[..open], delims, &mut found);
+            let body_start = open + delims.start.len();
+            let close = match rest[body_start..].find(delims.end) {
+                Some(close) => body_start + close,
+                None => {
+                    let kind = ErrorKind::Custom(UNTERMINATED);
+                    found.push(Diagnostic::new(content, &rest[open..], 1, &kind));
+                    return found;
+                }
+            };
+            if is_empty_block(&rest[body_start..close], delims) {
+                let kind = ErrorKind::Custom(EMPTY_BLOCK);
+                found.push(Diagnostic::new(content, &rest[open..], 1, &kind));
+            }
+            rest = &rest[close + delims.end.len()..];
+        }
+    }
+
+    /// Whether the text between a start and end delimiter holds nothing but blank comment lines
+    pub fn is_empty_block(body: &str, delims: Delimiters) -> bool {
+        body.lines()
+            .all(|x| strip_comment(x, delims).unwrap_or(x).trim().is_empty())
+    }
+
+    /// Add a `Diagnostic` for every comment line in `between` that starts with a known or configured tag
+    fn stray_tags(content: &str, between: &str, delims: Delimiters, found: &mut Vec<Diagnostic>) {
+        for line in between.lines() {
+            let text = match strip_comment(line, delims) {
+                Some(text) => text.trim_start(),
+                None => continue,
+            };
+            let word = text.split_whitespace().next().unwrap_or_default();
+            let tagged = text.starts_with(delims.params)
+                || text.starts_with(delims.ret)
+                || KNOWN_TAGS.contains(&word)
+                || word
+                    .strip_prefix('@')
+                    .is_some_and(|x| delims.tags.iter().any(|tag| tag.name == x));
+            if tagged {
+                let kind = ErrorKind::Custom(STRAY_TAG);
+                found.push(Diagnostic::new(content, text, 1, &kind));
+            }
+        }
+    }
+
+    impl fmt::Display for Diagnostic {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let gutter = " ".repeat(self.line.to_string().len());
+            writeln!(f, "error: expected {}", self.expected)?;
+            writeln!(
+                f,
+                "{}--> {}:{}:{}",
+                gutter, self.file, self.line, self.column
+            )?;
+            writeln!(f, "{} |", gutter)?;
+            writeln!(f, "{} | {}", self.line, self.snippet)?;
+            write!(
+                f,
+                "{} | {}{}",
+                gutter,
+                " ".repeat(self.column - 1),
+                "^".repeat(self.width)
+            )
+        }
+    }
+}
+
+/// Functions and declarations for decoding files that are not UTF-8
+mod encoding {
+    use super::*;
+    use std::str::FromStr;
+
+    /// Names accepted by `--encoding`
+    pub const ENCODINGS: &[&str] = &["utf-8", "latin1", "windows-1252", "utf-16le", "utf-16be"];
+
+    /// The characters windows-1252 puts at bytes `0x80` to `0x9F`, where latin1 has control codes
+    const WINDOWS_1252: [char; 32] = [
+        '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}',
+        '\u{2021}', '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}',
+        '\u{8F}', '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}',
+        '\u{2014}', '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}',
+        '\u{178}',
+    ];
+
+    /// How many leading bytes are checked for a NUL when deciding whether a file is binary
+    const BINARY_CHECK_LEN: usize = 8000;
+
+    /// Represents the character encoding of an input file
+    #[derive(Debug, Clone, Copy, PartialEq, Default)]
+    pub enum Encoding {
+        #[default]
+        Utf8,
+        Latin1,
+        Windows1252,
+        Utf16Le,
+        Utf16Be,
+    }
+
+    impl FromStr for Encoding {
+        type Err = String;
+
+        fn from_str(name: &str) -> Result<Encoding, String> {
+            match name.to_lowercase().replace('_', "-").as_str() {
+                "utf-8" | "utf8" => Ok(Encoding::Utf8),
+                "latin1" | "latin-1" | "iso-8859-1" => Ok(Encoding::Latin1),
+                "windows-1252" | "cp1252" => Ok(Encoding::Windows1252),
+                "utf-16le" => Ok(Encoding::Utf16Le),
+                "utf-16be" => Ok(Encoding::Utf16Be),
+                _ => Err(format!(
+                    "Unknown encoding \"{}\", expected one of {}",
+                    name,
+                    ENCODINGS.join(", ")
+                )),
+            }
+        }
+    }
+
+    impl fmt::Display for Encoding {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let name = match self {
+                Encoding::Utf8 => "utf-8",
+                Encoding::Latin1 => "latin1",
+                Encoding::Windows1252 => "windows-1252",
+                Encoding::Utf16Le => "utf-16le",
+                Encoding::Utf16Be => "utf-16be",
+            };
+            write!(f, "{}", name)
+        }
+    }
+
+    impl Encoding {
+        /// The encoding given by a byte order mark at the start of `bytes`, if any
+        pub fn from_bom(bytes: &[u8]) -> Option<Encoding> {
+            match bytes {
+                [0xEF, 0xBB, 0xBF, ..] => Some(Encoding::Utf8),
+                [0xFF, 0xFE, ..] => Some(Encoding::Utf16Le),
+                [0xFE, 0xFF, ..] => Some(Encoding::Utf16Be),
+                _ => None,
+            }
+        }
+
+        /// Decode `bytes`, replacing anything invalid, and report whether anything was replaced
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// Encoding::Utf8.decode(b"caf\xe9") // returns ("caf\u{FFFD}", true)
+        /// ```
+        pub fn decode(self, bytes: &[u8]) -> (String, bool) {
+            match self {
+                Encoding::Utf8 => {
+                    let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+                    match String::from_utf8_lossy(bytes) {
+                        Cow::Borrowed(text) => (text.to_string(), false),
+                        Cow::Owned(text) => (text, true),
+                    }
+                }
+                Encoding::Latin1 => (bytes.iter().map(|&b| b as char).collect(), false),
+                Encoding::Windows1252 => {
+                    let text = bytes
+                        .iter()
+                        .map(|&b| match b {
+                            0x80..=0x9F => WINDOWS_1252[usize::from(b - 0x80)],
+                            _ => b as char,
+                        })
+                        .collect();
+                    (text, false)
+                }
+                Encoding::Utf16Le | Encoding::Utf16Be => {
+                    let units = bytes.chunks(2).map(|x| match (self, x) {
+                        (Encoding::Utf16Le, [lo, hi]) => u16::from_le_bytes([*lo, *hi]),
+                        (_, [hi, lo]) => u16::from_be_bytes([*hi, *lo]),
+                        _ => 0xFFFD,
+                    });
+                    let mut lossy = !bytes.len().is_multiple_of(2);
+                    let text: String = std::char::decode_utf16(units)
+                        .map(|x| {
+                            x.unwrap_or_else(|_| {
+                                lossy = true;
+                                std::char::REPLACEMENT_CHARACTER
+                            })
+                        })
+                        .collect();
+                    (text.trim_start_matches('\u{FEFF}').to_string(), lossy)
+                }
+            }
+        }
+
+        /// Whether a NUL byte means a file is binary rather than text in this encoding
+        fn nul_is_binary(self) -> bool {
+            self != Encoding::Utf16Le && self != Encoding::Utf16Be
+        }
+    }
+
+    /// Whether `bytes` look like a binary file rather than text in `encoding`
+    pub fn is_binary(bytes: &[u8], encoding: Encoding) -> bool {
+        encoding.nul_is_binary() && bytes.iter().take(BINARY_CHECK_LEN).any(|&b| b == 0)
+    }
+
+    /// Read a file as text, or `None` with a warning if it looks binary
+    ///
+    /// A byte order mark takes priority over `encoding`, and anything invalid is replaced with a warning.
+    pub fn read_source(p: &Path, encoding: Encoding) -> Result<Option<String>, Box<dyn Error>> {
+        let bytes = fs::read(p)?;
+        let encoding = Encoding::from_bom(&bytes).unwrap_or(encoding);
+        if is_binary(&bytes, encoding) {
+            eprintln!("warning: {}: skipping binary file", p.display());
+            return Ok(None);
+        }
+        let (text, lossy) = encoding.decode(&bytes);
+        if lossy {
+            eprintln!(
+                "warning: {}: replaced text that is not valid {}, set the encoding with --encoding",
+                p.display(),
+                encoding
+            );
+        }
+        Ok(Some(text))
+    }
+}
+
+/// Functions and declarations for DocFile's and parsing
+mod docfile {
+    use super::*;
+    use rayon::prelude::*;
+    /// Represents all documentation in a file
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    pub struct DocFile {
+        pub thedocs: Vec<Doc>,
+        pub variables: Vec<Doc>,
+        pub filename: String,
+        #[serde(skip)]
+        pub path: String,
+        pub header: Option<FileHeader>,
+        pub undocumented: Vec<Symbol>,
+        pub sections: Vec<Section>,
+        #[serde(skip)]
+        pub diagnostics: Vec<Diagnostic>,
+    }
+
+    /// Represents the names of the docs under one section heading, or before the first heading
+    #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+    pub struct Section {
+        pub name: Option<String>,
+        pub docs: Vec<String>,
+    }
+
+    impl DocFile {
+        /// Append the given `Doc` to this `AllDoc`
+        #[allow(dead_code)]
+        pub fn add(&mut self, doc: Doc) {
+            self.thedocs.push(doc)
+        }
+
+        /// Rebuild `sections` from the section of each `Doc`, keeping file order
+        pub fn group_sections(&mut self) {
+            let mut sections: Vec<Section> = Vec::new();
+            for doc in &self.thedocs {
+                match sections.last_mut() {
+                    Some(last) if last.name == doc.section => last.docs.push(doc.name.clone()),
+                    _ => sections.push(Section {
+                        name: doc.section.clone(),
+                        docs: vec![doc.name.clone()],
+                    }),
+                }
+            }
+            self.sections = sections;
+        }
+    }
+
+    pub type Span<'a> = LocatedSpan<CompleteStr<'a>>;
+    /// Represents the string extracted from a file, including it's location in the file found.
+    pub struct Extracted {
+        pub content: String,
+        /// The text on the first line of the docstring up to the end of the start delimiter
+        pub prefix: String,
+        pub definition: Option<Symbol>,
+        pub block: Range,
+        pub definition_range: Option<Range>,
+        pub section: Option<String>,
+    }
+
+    /// The section named by a heading line, e.g. `# ALIASES`
+    ///
+    /// When the section delimiter is also the comment delimiter, only headings without lowercase letters count.
+    pub fn as_heading<'a>(line: &'a str, delims: Delimiters) -> Option<&'a str> {
+        let text = line.trim_start().strip_prefix(delims.section)?.trim();
+        let shouting = text.chars().any(char::is_alphabetic)
+            && text.chars().all(|c| {
+                c.is_uppercase() || c.is_ascii_digit() || c.is_whitespace() || "&-/,.".contains(c)
+            });
+        if text.is_empty() || (delims.section.trim() == delims.comm.trim() && !shouting) {
+            None
+        } else {
+            Some(text)
+        }
+    }
+
+    /// The last section heading in `between`, the text preceding a docstring, leaving out function bodies
+    fn last_heading(between: &str, delims: Delimiters) -> Option<String> {
+        top_level(between)
+            .into_iter()
+            .rev()
+            .find_map(|x| as_heading(x, delims))
+            .map(str::to_string)
+    }
+
+    /// The comment at the top of `contents`, after any `#!` line or modeline, when it stands apart from the code below it
+    pub fn leading_comment<'a>(contents: &'a str, delims: Delimiters) -> Option<&'a str> {
+        let mut begin = skip_modeline(contents);
+        let mut lines = contents[begin..].split_inclusive('\n').peekable();
+        while let Some(line) = lines.next_if(|x| x.starts_with("#!") || x.trim().is_empty()) {
+            begin += line.len();
+        }
+        let mut end = begin;
+        while let Some(line) = lines.next_if(|x| {
+            strip_comment(x, delims).is_some()
+                && !x.trim_start().starts_with(delims.start)
+                && as_heading(x, delims).is_none()
+        }) {
+            end += line.len();
+        }
+        let apart = lines
+            .peek()
+            .is_none_or(|x| x.trim().is_empty() || as_heading(x, delims).is_some());
+        if end > begin && apart {
+            Some(&contents[begin..end])
+        } else {
+            None
+        }
+    }
+
+    /// Nom function to extract all docstring from a file.
+    pub fn parse_strings_from_file<'a>(
+        input: Span<'a>,
+        delims: Delimiters,
+    ) -> IResult<Span<'a>, Vec<Extracted>> {
+        many0!(
+            input,
+            do_parse!(
+                between: complete!(take_until!(delims.start))
+                    >> begin: position!()
+                    >> content:
+                        complete!(preceded!(
+                            tag!(delims.start),
+                            take_until_and_consume!(delims.end)
+                        ))
+                    >> pos: position!()
+                    >> following: peek!(rest)
+                    >> (Extracted {
+                        content: content.to_string(),
+                        prefix: format!(
+                            "{}{}",
+                            &between.fragment[between.fragment.rfind('\n').map_or(0, |x| x + 1)..],
+                            delims.start
+                        ),
+                        definition: next_symbol(&following.fragment, pos.line),
+                        block: Range {
+                            start: Point {
+                                line: begin.line,
+                                column: begin.get_utf8_column()
+                            },
+                            end: Point {
+                                line: pos.line,
+                                column: pos.get_utf8_column() - 1
+                            }
+                        },
+                        definition_range: definition_range(&following.fragment, pos.line),
+                        section: last_heading(&between.fragment, delims)
+                    })
+            )
+        )
+    }
+
+    /// Gets all `START_DELIM->END_DELIM` comments in the zshrc
+    ///
+    /// `delims` must already include any modeline in `contents`, see `with_modeline`.
+    ///
+    /// This goes through every line finding the start of the docstring
+    /// and adds every line to a `Vec` until the end delimiter.
+    ///
+    /// A final `Vec` of the collected comment strings is returned.
+    pub fn get_strings(
+        contents: &str,
+        delims: Delimiters,
+    ) -> Result<Vec<Extracted>, Box<dyn Error>> {
+        let input = Span::new(CompleteStr(contents)).slice(skip_modeline(contents)..);
+        let mut x = parse_strings_from_file(input, delims)
+            .map_err(|e| e.to_string())?
+            .1;
+        let mut section = None;
+        for extracted in &mut x {
+            if extracted.section.is_some() {
+                section = extracted.section.clone();
+            }
+            extracted.section = section.clone();
+        }
+        Ok(x)
+    }
+
+    /// Gets every structural problem with the docstrings in the contents of the file at `p`
+    pub fn get_lints(contents: &str, p: &Path, delims: Delimiters) -> Vec<Diagnostic> {
+        lint(contents, delims)
+            .into_iter()
+            .map(|x| Diagnostic {
+                file: p.display().to_string(),
+                ..x
+            })
+            .collect()
+    }
+
+    /// Given a `Vec<str>` make a `DocFile`
+    pub fn generate_doc_file(docs: &[Extracted], fname: &Path, delims: Delimiters) -> DocFile {
+        let mut all_docs: DocFile = DocFile {
+            filename: String::from(fname.file_stem().unwrap().to_str().unwrap()),
+            path: fname.display().to_string(),
+            ..Default::default()
+        };
+        let header = docs.iter().position(|x| is_file_block(&x.content, delims));
+        all_docs.header = header.map(|i| as_header(&docs[i].content, delims));
+        let collected: Vec<Result<Doc, Diagnostic>> = docs
+            .par_iter()
+            .enumerate()
+            .filter(|(i, x)| !is_empty_block(&x.content, delims) && Some(*i) != header)
+            .map(|(_, x)| x)
+            .map(|x| Doc::make_doc(x, delims))
+            .collect();
+        for result in collected {
+            match result {
+                Ok(doc) if doc.is_variable() => all_docs.variables.push(doc),
+                Ok(doc) => all_docs.thedocs.push(doc),
+                Err(diagnostic) => all_docs.diagnostics.push(Diagnostic {
+                    file: fname.display().to_string(),
+                    ..diagnostic
+                }),
+            }
+        }
+        all_docs.group_sections();
+        all_docs
+    }
+
+    /// Every file named by `p`, a file, a directory or a glob pattern
+    fn extract_all_paths(p: Cow<str>) -> Result<Vec<PathBuf>, String> {
+        let pattern = if p.contains('*') {
+            expand_home(p)
+        } else {
+            let path = make_path(p);
+            if !path.is_dir() {
+                return Ok(vec![path]);
+            }
+            path.join("*")
+        };
+        let files = glob(pattern.to_str().unwrap())
+            .map_err(|e| e.to_string())?
+            .filter_map(|x| x.ok())
+            .filter(|x| x.is_file())
+            .collect();
+        Ok(files)
+    }
+
+    /// Generate the `DocFile` for the contents of the file at `p`, applying any modeline on top of `delims`
+    pub fn parse_source(
+        contents: &str,
+        p: &Path,
+        delims: Delimiters,
+        strict: bool,
+    ) -> Result<DocFile, Box<dyn Error>> {
+        let delims = with_modeline(contents, delims);
+        let docs = get_strings(contents, delims)?;
+        let mut dfile = generate_doc_file(&docs, p, delims);
+        if dfile.header.is_none() {
+            dfile.header = leading_comment(contents, delims).map(|x| as_header(x, delims));
+        }
+        dfile.undocumented = scan_definitions(contents, &dfile.thedocs)
+            .into_iter()
+            .filter(|x| !x.documented)
+            .map(|x| x.symbol)
+            .collect();
+        if strict {
+            dfile.diagnostics.extend(get_lints(contents, p, delims));
+        }
+        Ok(dfile)
+    }
+
+    /// Given a file path and delimiters, generate a DocFile for all files requested.
+    ///
+    /// The delimiters for each file are chosen by `pick`.
+    pub fn start<'a>(
+        p: Cow<str>,
+        pick: impl Fn(&Path) -> Delimiters<'a> + Sync,
+        encoding: Encoding,
+        strict: bool,
+        hide_deprecated: bool,
+    ) -> Result<Vec<DocFile>, String> {
+        let x: Vec<PathBuf> = extract_all_paths(p).map_err(|e| e.to_string())?;
+        let mut files: Vec<DocFile> = x
+            .par_iter()
+            .filter_map(|entry| {
+                let contents = match read_source(entry, encoding) {
+                    Ok(o) => o?,
+                    Err(e) => {
+                        println!("{}", e);
+                        exit(1);
+                    }
+                };
+                match parse_source(&contents, entry, pick(entry), strict) {
+                    Ok(dfile) => Some(dfile),
+                    Err(e) => {
+                        println!("{}", e);
+                        exit(1);
+                    }
+                }
+            })
+            .collect();
+        for dfile in &mut files {
+            dfile.diagnostics.iter().for_each(|x| eprintln!("{}", x));
+            if hide_deprecated {
+                dfile.thedocs.retain(|x| x.deprecated.is_none());
+                dfile.variables.retain(|x| x.deprecated.is_none());
+                dfile.group_sections();
+            }
+        }
+        resolve_references(&mut files);
+        Ok(files)
+    }
+
+    /// Point every `@see` at the file and line where its name is documented, warning about any that are not found
+    pub fn resolve_references(files: &mut [DocFile]) {
+        let mut known: HashMap<String, (String, String, u32)> = HashMap::new();
+        for dfile in files.iter() {
+            for doc in dfile.thedocs.iter().chain(&dfile.variables) {
+                known
+                    .entry(doc.name.clone())
+                    .or_insert_with(|| (dfile.filename.clone(), dfile.path.clone(), doc.line()));
+            }
+        }
+        for dfile in files.iter_mut() {
+            for doc in dfile.thedocs.iter_mut().chain(dfile.variables.iter_mut()) {
+                for reference in &mut doc.see {
+                    match known.get(&reference.name) {
+                        Some((file, path, line)) => {
+                            reference.file = Some(file.clone());
+                            reference.path = Some(path.clone());
+                            reference.line = Some(*line);
+                        }
+                        None => eprintln!(
+                            "warning: {}:{}: @see {} does not match any documented name",
+                            dfile.path, reference.source_line, reference.name
+                        ),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Functions for presenting bashdocs to STDOUT, as JSON, or HTML
+mod outputs {
+    use super::*;
+    use colored::*;
+    use std::io::prelude::*;
+    /// The name of the section that the `i`th doc begins, if it is the first doc of a named section
+    fn starts_section(thedocs: &DocFile, i: usize) -> Option<&str> {
+        let section = thedocs.thedocs[i].section.as_deref()?;
+        match i.checked_sub(1) {
+            Some(prev) if thedocs.thedocs[prev].section.as_deref() == Some(section) => None,
+            _ => Some(section),
+        }
+    }
+
+    /// Pretty print an `DocFile`
+    ///
+    /// Given an `AllDoc`:
+    /// ```
+    ///[
+    ///    Doc {
+    ///        short_description: "runner()",
+    ///        long_description: "This is the beginning",
+    ///        descriptors: {
+    ///            "CTRL-O": "pushs the boundaries"
+    ///        },
+    ///        params: {},
+    ///        returns: {}
+    ///    },
+    ///    Doc {
+    ///        short_description: "runner()",
+    ///        long_description: "This is the beginning",
+    ///        descriptors: {},
+    ///        params: {
+    ///            "location": "where to put it",
+    ///            "filename": "don\'t test me"
+    ///        },
+    ///        returns: {
+    ///            "nothing": ""
+    ///        }
+    ///    }
+    ///]
+    /// ```
+    /// The following will be printed to the `STDOUT` with color
+    /// ```
+    /// Help
+    /// runner: This is the beginning
+    ///     CTRL-O pushs the boundaries
+    /// runner <location> <filename>: This is the beginning
+    /// ```
+    pub fn printer(thedocs: &DocFile, use_color: bool) {
+        if use_color {
+            print!(
+                "{}: {}",
+                "Help".green().underline(),
+                thedocs.filename.green().underline()
+            );
+            match &thedocs.header {
+                Some(header) => {
+                    println!(" - {}", header.summary.bold());
+                    header
+                        .description
+                        .split("\n\n")
+                        .filter(|x| !x.is_empty())
+                        .for_each(|x| println!("\t{}", x));
+                    header
+                        .details()
+                        .iter()
+                        .for_each(|(label, x)| println!("\t{}: {}", label.yellow().bold(), x));
+                }
+                None => println!(),
+            }
+        } else {
+            print!("Help: {}", thedocs.filename);
+            match &thedocs.header {
+                Some(header) => {
+                    println!(" - {}", header.summary);
+                    header
+                        .description
+                        .split("\n\n")
+                        .filter(|x| !x.is_empty())
+                        .for_each(|x| println!("\t{}", x));
+                    header
+                        .details()
+                        .iter()
+                        .for_each(|(label, x)| println!("\t{}: {}", label, x));
+                }
+                None => println!(),
+            }
+        }
+        for (i, doc) in thedocs.thedocs.iter().enumerate() {
+            if let Some(section) = starts_section(thedocs, i) {
+                if use_color {
+                    println!("\n{}", section.bold().underline());
+                } else {
+                    println!("\n{}", section);
+                }
+            }
+            let summary = doc
+                .long_description
+                .split("\n\n")
+                .next()
+                .unwrap_or_default();
+            if use_color {
+                if doc.deprecated.is_some() {
+                    print!("{}", doc.name.blue().bold().strikethrough());
+                } else {
+                    print!("{}", doc.name.blue().bold());
+                }
+                if doc.params.is_empty() {
+                    println!(": {}", summary);
+                } else {
+                    let params: Vec<String> = doc.params.iter().map(Param::usage).collect();
+                    println!(" {}: {}", params.join(" ").cyan(), summary);
+                }
+            } else {
+                println!("{}: {}", doc.usage(), summary);
+            }
+            print_details(doc, use_color);
+        }
+        print_variables(thedocs, use_color);
+    }
+
+    /// Print everything in a `Doc` below its summary line, indented by a tab
+    fn print_details(doc: &Doc, use_color: bool) {
+        doc.long_description
+            .split("\n\n")
+            .skip(1)
+            .for_each(|x| println!("\t{}", x));
+        if use_color {
+            if let Some(deprecation) = &doc.deprecated {
+                println!("\t{}", deprecation.to_string().red());
+            }
+            doc.descriptors
+                .iter()
+                .for_each(|x| println!("\t{} {}", &x.key.yellow().bold(), x.value));
+            for example in &doc.examples {
+                println!("\t{} {}", "Example:".magenta().bold(), example.description);
+                example.code.lines().for_each(|x| println!("\t    {}", x));
+                if let Some(output) = &example.output {
+                    output
+                        .lines()
+                        .for_each(|x| println!("\t    {} {}", "=>".dimmed(), x));
+                }
+            }
+            for (name, value) in &doc.tags {
+                println!("\t{}: {}", name.yellow(), value);
+            }
+            for env in &doc.env {
+                println!("\t{} {}", format!("${}", env.key).green(), env.value);
+            }
+            for (stream, text) in doc.outputs() {
+                println!("\t{:<8}{}", stream.green().bold(), text);
+            }
+            if !doc.exit_codes.is_empty() {
+                println!("\t{:<8}{}", "Exit".underline(), "Meaning".underline());
+            }
+            for code in &doc.exit_codes {
+                println!(
+                    "\t{:<8}{}",
+                    code.code.to_string().green().bold(),
+                    code.meaning
+                );
+            }
+            for reference in &doc.see {
+                match (&reference.path, reference.line) {
+                    (Some(path), Some(line)) => println!(
+                        "\t{} {} ({}:{})",
+                        "See:".magenta().bold(),
+                        reference.name,
+                        path,
+                        line
+                    ),
+                    _ => println!("\t{} {}", "See:".magenta().bold(), reference.name),
+                }
+            }
+        } else {
+            if let Some(deprecation) = &doc.deprecated {
+                println!("\t[{}]", deprecation);
+            }
+            doc.descriptors
+                .iter()
+                .for_each(|x| println!("\t{} {}", &x.key, x.value));
+            for example in &doc.examples {
+                println!("\tExample: {}", example.description);
+                example.code.lines().for_each(|x| println!("\t    {}", x));
+                if let Some(output) = &example.output {
+                    output.lines().for_each(|x| println!("\t    => {}", x));
+                }
+            }
+            for (name, value) in &doc.tags {
+                println!("\t{}: {}", name, value);
+            }
+            for env in &doc.env {
+                println!("\t${} {}", env.key, env.value);
+            }
+            for (stream, text) in doc.outputs() {
+                println!("\t{:<8}{}", stream, text);
+            }
+            if !doc.exit_codes.is_empty() {
+                println!("\tExit    Meaning");
+            }
+            for code in &doc.exit_codes {
+                println!("\t{:<8}{}", code.code, code.meaning);
+            }
+            for reference in &doc.see {
+                match (&reference.path, reference.line) {
+                    (Some(path), Some(line)) => {
+                        println!("\tSee: {} ({}:{})", reference.name, path, line)
+                    }
+                    _ => println!("\tSee: {}", reference.name),
+                }
+            }
+        }
+    }
+
+    /// Print the variables documented in a `DocFile`, after its functions and aliases
+    ///
+    /// ```
+    /// Variables
+    /// export EDITOR=vim: preferred editor
+    /// ```
+    fn print_variables(thedocs: &DocFile, use_color: bool) {
+        if thedocs.variables.is_empty() {
+            return;
+        }
+        if use_color {
+            println!("\n{}", "Variables".bold().underline());
+        } else {
+            println!("\nVariables");
+        }
+        for doc in &thedocs.variables {
+            let summary = doc
+                .long_description
+                .split("\n\n")
+                .next()
+                .unwrap_or_default();
+            if use_color {
+                println!("{}: {}", doc.declaration().blue().bold(), summary);
+            } else {
+                println!("{}: {}", doc.declaration(), summary);
+            }
+            print_details(doc, use_color);
+        }
+    }
+
+    /// Print every function and alias in a `DocFile` that has no docstring
+    ///
+    /// ```
+    /// Undocumented: zshrc
+    /// serve (alias) line 40
+    /// myhelp (function) line 102
+    /// ```
+    pub fn print_undocumented(thedocs: &DocFile, use_color: bool) {
+        if use_color {
+            println!(
+                "{}: {}",
+                "Undocumented".red().underline(),
+                thedocs.filename.red().underline()
+            );
+        } else {
+            println!("Undocumented: {}", thedocs.filename);
+        }
+        for symbol in &thedocs.undocumented {
+            let kind = symbol.kind.to_string();
+            if use_color {
+                println!(
+                    "{} ({}) line {}",
+                    symbol.name.blue().bold(),
+                    kind.cyan(),
+                    symbol.line
+                );
+            } else {
+                println!("{} ({}) line {}", symbol.name, kind, symbol.line);
+            }
+        }
+    }
+
+    /// Map every environment variable named by an `@env` to the functions that use it
+    pub fn env_usage(docstrings: &[DocFile]) -> BTreeMap<String, Vec<String>> {
+        let mut result: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for dfile in docstrings {
+            for doc in &dfile.thedocs {
+                for env in &doc.env {
+                    result
+                        .entry(env.key.clone())
+                        .or_default()
+                        .push(format!("{} ({})", doc.name, dfile.filename));
+                }
+            }
+        }
+        result
+    }
+
+    /// Print every environment variable used across all `DocFile`s and the functions that use it
+    ///
+    /// ```
+    /// Environment
+    /// $EDITOR: fs (zshrc), xi (zshrc)
+    /// ```
+    pub fn print_env_report(docstrings: &[DocFile], use_color: bool) {
+        if use_color {
+            println!("{}", "Environment".green().underline());
+        } else {
+            println!("Environment");
+        }
+        for (name, users) in env_usage(docstrings) {
+            let name = format!("${}", name);
+            if use_color {
+                println!("{}: {}", name.blue().bold(), users.join(", "));
+            } else {
+                println!("{}: {}", name, users.join(", "));
+            }
+        }
+    }
+
+    /// Given a list of `DocFile` and a file path, write the JSON representation to a file.
     pub fn write_json(docstrings: &[DocFile], file_name: &str) {
         let mut map = HashMap::new();
         map.insert("docs", docstrings);
@@ -441,218 +2237,1638 @@ mod outputs {
         let path_as_str = if cfg!(windows) {
             String::from(file_name)
         } else {
-            file_name.replace("~", home_dir().unwrap().to_str().unwrap())
+            file_name.replace("~", home_dir().unwrap().to_str().unwrap())
+        };
+        let path = Path::new(&path_as_str);
+        let mut file = File::create(Path::new(&path)).expect("Invalid file path.");
+        file.write_all(json.as_bytes())
+            .expect("Could not write to file.");
+    }
+
+    pub fn to_html(docstrings: &[DocFile], dir: Option<&str>, template_loc: Option<&str>) {
+        for dfile in docstrings {
+            let json = to_json(dfile);
+            let handlebars = Handlebars::new();
+            let mut template = match template_loc {
+                Some(m) => match File::open(m) {
+                    Ok(o) => o,
+                    Err(_) => {
+                        std::dbg!("Provided path is invalid");
+                        exit(1);
+                    }
+                },
+                None => File::open("./static/template.hbs").unwrap(),
+            };
+            // let mut template = File::open("./static/template.hbs").unwrap();
+            let mut output = match dir {
+                Some(d) if Path::new(d).is_dir() => {
+                    File::create(format!("{}/{}.html", d, dfile.filename).as_str())
+                        .expect("File could not be created")
+                }
+                None | Some(_) => {
+                    std::dbg!("Provided path is invalid");
+                    exit(1);
+                }
+            };
+            // let mut output = if dir.len() == 1 {
+            //     File::create(format!("{}.html", dfile.filename).as_str())
+            //         .expect("File cannot be created")
+            // } else {
+            //     File::create(format!("{}/{}.html", dir, dfile.filename).as_str())
+            //         .expect("File cannot be created")
+            // };
+            handlebars
+                .render_template_source_to_write(&mut template, &json, &mut output)
+                .expect("Could not generate documentation");
+        }
+    }
+}
+
+/// Functions and declarations for generating/overriding delimiters
+mod delims {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    /// Represents the necessary delimiters for a `bashdoc`
+    #[derive(Debug, Serialize, Deserialize, Copy, Clone)]
+    pub struct Delimiters<'a> {
+        pub start: &'a str,
+        pub end: &'a str,
+        pub params: &'a str,
+        pub ret: &'a str,
+        pub opt: &'a str,
+        pub comm: &'a str,
+        pub section: &'a str,
+        #[serde(skip)]
+        pub tags: &'a [CustomTag],
+    }
+
+    /// Whether a user-defined tag holds a single value or collects a list
+    #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    pub enum TagKind {
+        Single,
+        List,
+    }
+
+    /// Represents a user-defined tag, declared in the `[tags]` table of `.bashdocrc`
+    ///
+    /// ```toml
+    /// [tags]
+    /// owner = "single"
+    /// requires = "list"
+    /// ```
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    pub struct CustomTag {
+        pub name: String,
+        pub kind: TagKind,
+    }
+
+    /// Represents a set of delimiters in `.bashdocrc`, at the top level or under `[profiles.<name>]`
+    ///
+    /// Delimiters that are left out are taken from the preset for `lang`, or from the defaults.
+    #[derive(Debug, Serialize, Deserialize, Clone, Default)]
+    pub struct Profile {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub lang: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub start: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub end: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub params: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub ret: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub opt: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub comm: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub section: Option<String>,
+    }
+
+    impl Profile {
+        /// The delimiters set in this profile, without any user-defined tags
+        pub fn delims(&self) -> Delimiters<'_> {
+            let preset = match &self.lang {
+                Some(lang) => match Delimiters::preset(lang) {
+                    Some(preset) => preset,
+                    None => {
+                        eprintln!(
+                            "Unknown lang \"{}\" in .bashdocrc, expected one of {}",
+                            lang,
+                            LANGUAGES.join(", ")
+                        );
+                        exit(1);
+                    }
+                },
+                None => Delimiters::default(),
+            };
+            Delimiters {
+                start: self.start.as_deref().unwrap_or(preset.start),
+                end: self.end.as_deref().unwrap_or(preset.end),
+                params: self.params.as_deref().unwrap_or(preset.params),
+                ret: self.ret.as_deref().unwrap_or(preset.ret),
+                opt: self.opt.as_deref().unwrap_or(preset.opt),
+                comm: self.comm.as_deref().unwrap_or(preset.comm),
+                section: self.section.as_deref().unwrap_or(preset.section),
+                tags: &[],
+            }
+        }
+    }
+
+    /// Represents a `[[rules]]` entry of `.bashdocrc`, using `profile` for files matching `glob`
+    #[derive(Debug, Serialize, Deserialize, Clone)]
+    pub struct ProfileRule {
+        pub glob: String,
+        pub profile: String,
+    }
+
+    /// Represents the contents of `.bashdocrc`, which `Delimiters` borrow from
+    ///
+    /// `profile` names the profile used by default, in place of the top level delimiters.
+    #[derive(Debug, Serialize, Deserialize, Clone)]
+    pub struct Config {
+        #[serde(flatten)]
+        pub delimiters: Profile,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub profile: Option<String>,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub tags: BTreeMap<String, TagKind>,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub languages: BTreeMap<String, String>,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub profiles: BTreeMap<String, Profile>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub rules: Vec<ProfileRule>,
+    }
+
+    impl Default for Config {
+        fn default() -> Config {
+            let delims = Delimiters::default();
+            Config {
+                delimiters: Profile {
+                    lang: None,
+                    start: Some(delims.start.to_string()),
+                    end: Some(delims.end.to_string()),
+                    params: Some(delims.params.to_string()),
+                    ret: Some(delims.ret.to_string()),
+                    opt: Some(delims.opt.to_string()),
+                    comm: Some(delims.comm.to_string()),
+                    section: Some(delims.section.to_string()),
+                },
+                profile: None,
+                tags: BTreeMap::new(),
+                languages: BTreeMap::new(),
+                profiles: BTreeMap::new(),
+                rules: Vec::new(),
+            }
+        }
+    }
+
+    impl Config {
+        /// Read/Write contents of `$BASHDOC_CONFIG_PATH` for use as Delimiters.
+        pub fn get_config() -> Self {
+            match read_config() {
+                Some(contents) => toml::from_str(&contents).unwrap(),
+                None => {
+                    let config = Config::default();
+                    let content =
+                        toml::to_string_pretty(&config).expect("Could not be converted to TOML");
+                    let mut path = home_dir().unwrap();
+                    path.push(".bashdocrc");
+                    fs::write(path.to_str().unwrap(), content).unwrap();
+                    env::set_var("BASHDOC_CONFIG_PATH", path);
+                    config
+                }
+            }
+        }
+
+        /// The profile called `name`, or the default profile when `name` is `None`
+        pub fn profile(&self, name: Option<&str>) -> &Profile {
+            match name.or(self.profile.as_deref()) {
+                Some(name) => match self.profiles.get(name) {
+                    Some(profile) => profile,
+                    None => {
+                        eprintln!("No profile \"{}\" in .bashdocrc", name);
+                        exit(1);
+                    }
+                },
+                None => &self.delimiters,
+            }
+        }
+
+        /// The delimiters of the default profile, without any user-defined tags
+        pub fn delims(&self) -> Delimiters<'_> {
+            self.profile(None).delims()
+        }
+
+        /// The delimiters for a file, from the first rule whose glob matches it or else from its language
+        pub fn delims_for(&self, p: &Path) -> Delimiters<'_> {
+            let relative = env::current_dir()
+                .ok()
+                .and_then(|x| p.strip_prefix(x).ok().map(Path::to_path_buf))
+                .unwrap_or_else(|| p.to_path_buf());
+            let rule = self
+                .rules
+                .iter()
+                .find(|rule| match Pattern::new(&rule.glob) {
+                    Ok(pattern) => pattern.matches_path(&relative) || pattern.matches_path(p),
+                    Err(e) => {
+                        eprintln!("Invalid glob \"{}\" in .bashdocrc: {}", rule.glob, e);
+                        exit(1);
+                    }
+                });
+            match rule {
+                Some(rule) => self.profile(Some(&rule.profile)).delims(),
+                None => detect_delims(p, &self.languages, self.delims()),
+            }
+        }
+
+        /// The user-defined tags declared in this config, if any.
+        pub fn custom_tags(&self) -> Vec<CustomTag> {
+            self.tags
+                .iter()
+                .map(|(name, kind)| CustomTag {
+                    name: name.trim_start_matches('@').to_string(),
+                    kind: *kind,
+                })
+                .collect()
+        }
+    }
+
+    /// Read the contents of `./.bashdocrc`, falling back to `$BASHDOC_CONFIG_PATH`.
+    fn read_config() -> Option<String> {
+        let local = env::current_dir().unwrap().join(".bashdocrc");
+        if local.is_file() {
+            Some(fs::read_to_string(local).expect("could not read from file."))
+        } else {
+            env::var_os("BASHDOC_CONFIG_PATH")
+                .map(|val| fs::read_to_string(val).expect("could not read from file."))
+        }
+    }
+
+    /// Names accepted by `--lang` and the `lang` key of `.bashdocrc`
+    pub const LANGUAGES: &[&str] = &[
+        "bash",
+        "zsh",
+        "sh",
+        "fish",
+        "powershell",
+        "makefile",
+        "dockerfile",
+        "python",
+        "c",
+        "lua",
+    ];
+
+    /// Delimiters for languages with `#` comments
+    const HASH: Delimiters<'static> = Delimiters {
+        start: "#;",
+        end: "#\"",
+        params: "@param",
+        ret: "@return",
+        opt: "# -",
+        comm: "# ",
+        section: "# ",
+        tags: &[],
+    };
+
+    /// Delimiters for languages with `//` comments
+    const SLASH: Delimiters<'static> = Delimiters {
+        start: "//;",
+        end: "//\"",
+        params: "@param",
+        ret: "@return",
+        opt: "// -",
+        comm: "// ",
+        section: "// ",
+        tags: &[],
+    };
+
+    /// Delimiters for languages with `--` comments
+    const DASH: Delimiters<'static> = Delimiters {
+        start: "--;",
+        end: "--\"",
+        params: "@param",
+        ret: "@return",
+        opt: "-- -",
+        comm: "-- ",
+        section: "-- ",
+        tags: &[],
+    };
+
+    /// Find the language of a file from the `[languages]` table of `.bashdocrc`, its name or extension, or its `#!` line
+    ///
+    /// Keys of `languages` are matched against the file name and then its extension.
+    pub fn detect_lang(p: &Path, languages: &BTreeMap<String, String>) -> Option<String> {
+        let name = p.file_name().and_then(|x| x.to_str()).unwrap_or_default();
+        let extension = p.extension().and_then(|x| x.to_str());
+        let configured = languages
+            .get(name)
+            .or_else(|| extension.and_then(|x| languages.get(x.trim_start_matches('.'))));
+        if let Some(lang) = configured {
+            return Some(lang.clone());
+        }
+        lang_for_name(name)
+            .or_else(|| extension.and_then(lang_for_extension))
+            .or_else(|| first_line(p).as_ref().and_then(|x| lang_for_shebang(x)))
+            .map(str::to_string)
+    }
+
+    /// Choose the delimiters for a file, keeping `fallback` when its language comments the same way
+    pub fn detect_delims<'a>(
+        p: &Path,
+        languages: &BTreeMap<String, String>,
+        fallback: Delimiters<'a>,
+    ) -> Delimiters<'a> {
+        let lang = match detect_lang(p, languages) {
+            Some(lang) => lang,
+            None => return fallback,
         };
-        let path = Path::new(&path_as_str);
-        let mut file = File::create(Path::new(&path)).expect("Invalid file path.");
-        file.write_all(json.as_bytes())
-            .expect("Could not write to file.");
+        let preset = match Delimiters::preset(&lang) {
+            Some(preset) => preset,
+            None => {
+                eprintln!(
+                    "Unknown lang \"{}\" for {} in .bashdocrc, expected one of {}",
+                    lang,
+                    p.display(),
+                    LANGUAGES.join(", ")
+                );
+                exit(1);
+            }
+        };
+        if preset.comm.trim() == fallback.comm.trim() {
+            fallback
+        } else {
+            Delimiters {
+                tags: fallback.tags,
+                ..preset
+            }
+        }
+    }
+
+    fn lang_for_name(name: &str) -> Option<&'static str> {
+        match name.trim_start_matches('.') {
+            "Makefile" | "makefile" | "GNUmakefile" => Some("makefile"),
+            "bashrc" | "bash_profile" | "bash_aliases" | "profile" => Some("bash"),
+            "zshrc" | "zshenv" | "zprofile" | "zlogin" => Some("zsh"),
+            x if x == "Dockerfile" || x.starts_with("Dockerfile.") => Some("dockerfile"),
+            _ => None,
+        }
+    }
+
+    fn lang_for_extension(extension: &str) -> Option<&'static str> {
+        match extension {
+            "sh" => Some("sh"),
+            "bash" => Some("bash"),
+            "zsh" => Some("zsh"),
+            "fish" => Some("fish"),
+            "ps1" | "psm1" | "psd1" => Some("powershell"),
+            "mk" | "make" => Some("makefile"),
+            "dockerfile" => Some("dockerfile"),
+            "py" => Some("python"),
+            "c" | "h" | "cc" | "cpp" | "hpp" | "java" | "js" | "ts" | "go" | "rs" => Some("c"),
+            "lua" => Some("lua"),
+            _ => None,
+        }
+    }
+
+    /// Find the language run by a `#!` line, looking past `env` to the interpreter it runs
+    pub fn lang_for_shebang(line: &str) -> Option<&'static str> {
+        let mut words = line.strip_prefix("#!")?.split_whitespace();
+        let mut program = words.next()?.rsplit('/').next()?;
+        if program == "env" {
+            program = words.find(|x| !x.starts_with('-'))?;
+        }
+        match program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.') {
+            "sh" | "dash" | "ksh" => Some("sh"),
+            "bash" => Some("bash"),
+            "zsh" => Some("zsh"),
+            "fish" => Some("fish"),
+            "pwsh" | "powershell" => Some("powershell"),
+            "make" => Some("makefile"),
+            "python" => Some("python"),
+            "lua" => Some("lua"),
+            _ => None,
+        }
+    }
+
+    /// Read the first line of a file, if it can be read
+    fn first_line(p: &Path) -> Option<String> {
+        let file = File::open(p).ok()?;
+        let mut line = String::new();
+        BufReader::new(file).read_line(&mut line).ok()?;
+        Some(line)
+    }
+
+    const MODELINE: &str = "bashdoc:";
+    const MODELINE_LINES: usize = 5;
+    const MODELINE_KEYS: &[&str] = &[
+        "lang", "start", "end", "params", "ret", "opt", "comm", "section",
+    ];
+
+    /// Apply a `bashdoc:` modeline found in the first lines of `contents` on top of `delims`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// with_modeline("# bashdoc: start=\"##\" end=\"##.\"\n", delims) // returns Delimiters {start: "##", end: "##.", ..delims}
+    /// ```
+    pub fn with_modeline<'a>(contents: &'a str, delims: Delimiters<'a>) -> Delimiters<'a> {
+        let settings = match find_modeline(contents) {
+            Some((settings, _)) => settings,
+            None => return delims,
+        };
+        let mut result = delims;
+        for (key, value) in settings {
+            match key {
+                "lang" => {
+                    if let Some(preset) = Delimiters::preset(value) {
+                        result = Delimiters {
+                            tags: delims.tags,
+                            ..preset
+                        };
+                    }
+                }
+                "start" => result.start = value,
+                "end" => result.end = value,
+                "params" => result.params = value,
+                "ret" => result.ret = value,
+                "opt" => result.opt = value,
+                "comm" => result.comm = value,
+                "section" => result.section = value,
+                _ => {}
+            }
+        }
+        result
+    }
+
+    /// Where docstrings may begin in `contents`, after any `bashdoc:` modeline
+    pub fn skip_modeline(contents: &str) -> usize {
+        find_modeline(contents).map_or(0, |(_, end)| end)
+    }
+
+    /// Find a `bashdoc:` modeline in the first lines of `contents`, returning its settings and where its line ends
+    ///
+    /// A line only counts as a modeline when it sets at least one known key.
+    fn find_modeline(contents: &str) -> Option<(Vec<(&str, &str)>, usize)> {
+        let mut end = 0;
+        for line in contents.split_inclusive('\n').take(MODELINE_LINES) {
+            end += line.len();
+            if let Some(at) = line.find(MODELINE) {
+                if line[..at].chars().all(|c| !c.is_alphanumeric()) {
+                    let settings: Vec<_> = as_settings(line[at + MODELINE.len()..].trim_end())
+                        .into_iter()
+                        .filter(|(key, value)| MODELINE_KEYS.contains(key) && !value.is_empty())
+                        .collect();
+                    if !settings.is_empty() {
+                        return Some((settings, end));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Split `key="value"` pairs separated by whitespace, stopping at the first that is malformed
+    fn as_settings(input: &str) -> Vec<(&str, &str)> {
+        let mut result = Vec::new();
+        let mut rest = input.trim_start();
+        while let Some(eq) = rest.find("=\"") {
+            let key = &rest[..eq];
+            let value = &rest[eq + 2..];
+            let close = match value.find('"') {
+                Some(close) => close,
+                None => break,
+            };
+            if key.is_empty() || key.contains(char::is_whitespace) {
+                break;
+            }
+            result.push((key, &value[..close]));
+            rest = value[close + 1..].trim_start();
+        }
+        result
+    }
+
+    impl<'a> Default for Delimiters<'a> {
+        fn default() -> Delimiters<'a> {
+            HASH
+        }
+    }
+    impl<'a> Delimiters<'a> {
+        /// The built-in delimiters for one of `LANGUAGES`
+        pub fn preset(lang: &str) -> Option<Delimiters<'static>> {
+            match lang {
+                "bash" | "zsh" | "sh" | "fish" | "powershell" | "makefile" | "dockerfile"
+                | "python" => Some(HASH),
+                "c" => Some(SLASH),
+                "lua" => Some(DASH),
+                _ => None,
+            }
+        }
+
+        /// Override default delimiters with passed in values
+        pub fn override_delims(overrides: &'a ArgMatches<'a>) -> Self {
+            let mut result: Delimiters = Delimiters::default();
+            for key in overrides.args.keys() {
+                match *key {
+                    "start" => result.start = overrides.value_of(key).unwrap(),
+                    "end" => result.end = overrides.value_of(key).unwrap(),
+                    "descriptor" => result.opt = overrides.value_of(key).unwrap(),
+                    "params" => result.params = overrides.value_of(key).unwrap(),
+                    "returns" => result.ret = overrides.value_of(key).unwrap(),
+                    "comment" => result.comm = overrides.value_of(key).unwrap(),
+                    "section" => result.section = overrides.value_of(key).unwrap(),
+                    _ => {}
+                }
+            }
+            result
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    mod kv_tests {
+        use super::*;
+        #[test]
+        fn new_kv() {
+            let kv = KV::new(String::from("a"), String::from("b"));
+            assert_eq!(String::from("a"), kv.key);
+            assert_eq!(String::from("b"), kv.value);
+        }
+
+        #[test]
+        fn cmp_kv() {
+            let kv1 = KV::new(String::from("a"), String::from("b"));
+            let kv2 = KV::new(String::from("a"), String::from("b"));
+            let kv = KV::new(String::from("b"), String::from("a"));
+            assert_eq!(kv1, kv2);
+            assert_ne!(kv1, kv);
+        }
+
+        #[test]
+        fn is_as_kv() {
+            let conv = as_kv("type: mp4 or gif");
+            assert_eq!(
+                KV {
+                    key: String::from("type"),
+                    value: String::from("mp4 or gif")
+                },
+                conv.unwrap()
+            );
+        }
+
+        #[test]
+        fn is_as_kv_white() {
+            let conv = as_kv("CTRL-O to open with `open` command,");
+            assert_eq!(
+                KV {
+                    key: String::from("CTRL-O"),
+                    value: String::from("to open with `open` command,")
+                },
+                conv.unwrap()
+            );
+        }
+    }
+
+    mod param_tests {
+        use super::*;
+        #[test]
+        fn plain_param() {
+            let conv = as_param(" input: markdown file to convert").unwrap();
+            assert_eq!(
+                Param {
+                    key: String::from("input"),
+                    value: String::from("markdown file to convert"),
+                    ..Default::default()
+                },
+                conv
+            );
+            assert_eq!("<input>", conv.usage());
+        }
+
+        #[test]
+        fn typed_param() {
+            let conv = as_param(" {int} count=5 [optional]: how many").unwrap();
+            assert_eq!(
+                Param {
+                    key: String::from("count"),
+                    value: String::from("how many"),
+                    type_name: Some(String::from("int")),
+                    default: Some(String::from("5")),
+                    optional: true,
+                    variadic: false,
+                },
+                conv
+            );
+            assert_eq!("[count=5]", conv.usage());
+        }
+
+        #[test]
+        fn variadic_param() {
+            let conv = as_param(" files...").unwrap();
+            assert!(conv.variadic);
+            assert_eq!("files", conv.key);
+            assert_eq!("files...", conv.usage());
+        }
+
+        #[test]
+        fn usage_line() {
+            let sample = "
+        # mp()
+        # Convert from markdown to docx
+        # @param input: markdown file to convert
+        # @param {int} count=5: how many
+        # @param files...
+        ";
+            let mut doc = parse_doc(sample, Delimiters::default()).unwrap().1;
+            doc.name = String::from("mp");
+            assert_eq!("mp <input> [count=5] files...", doc.usage());
+        }
+    }
+
+    mod symbol_tests {
+        use super::*;
+        #[test]
+        fn variable_kinds() {
+            let cases = [
+                (
+                    "export PATH=$HOME/bin:$PATH",
+                    "PATH",
+                    SymbolKind::Export,
+                    Some("$HOME/bin:$PATH"),
+                ),
+                (
+                    "export EDITOR='vim'",
+                    "EDITOR",
+                    SymbolKind::Export,
+                    Some("vim"),
+                ),
+                ("export -n EDITOR", "EDITOR", SymbolKind::Export, None),
+                (
+                    "export EDITOR=\"vim\" # default",
+                    "EDITOR",
+                    SymbolKind::Export,
+                    Some("vim"),
+                ),
+                (
+                    "PROMPT='# ' # root",
+                    "PROMPT",
+                    SymbolKind::Assignment,
+                    Some("# "),
+                ),
+                ("URL=a#b", "URL", SymbolKind::Assignment, Some("a#b")),
+                (
+                    "readonly MAX_TRIES=3",
+                    "MAX_TRIES",
+                    SymbolKind::Readonly,
+                    Some("3"),
+                ),
+                (
+                    "declare -r -i COUNT=5",
+                    "COUNT",
+                    SymbolKind::Declare,
+                    Some("5"),
+                ),
+                (
+                    "ZSH_THEME=\"refined\"",
+                    "ZSH_THEME",
+                    SymbolKind::Assignment,
+                    Some("refined"),
+                ),
+            ];
+            for (line, name, kind, value) in cases.iter() {
+                assert_eq!(
+                    Some(Symbol {
+                        name: name.to_string(),
+                        kind: *kind,
+                        line: 0,
+                        value: value.map(str::to_string),
+                    }),
+                    parse_symbol(line)
+                );
+            }
+        }
+
+        #[test]
+        fn variables_listed_apart() {
+            let sample = "#;\n# ZSH\n# oh-my-zsh install\n#\"\nexport ZSH=\"$HOME/.oh-my-zsh\"\n#;\n# up()\n# go up\n#\"\nup() { cd ..; }\n";
+            let docs = get_strings(sample, Delimiters::default()).unwrap();
+            let dfile = generate_doc_file(&docs, Path::new("zshrc"), Delimiters::default());
+            assert_eq!(1, dfile.thedocs.len());
+            assert_eq!("up", dfile.thedocs[0].name);
+            assert_eq!(1, dfile.variables.len());
+            assert_eq!(
+                "export ZSH=$HOME/.oh-my-zsh",
+                dfile.variables[0].declaration()
+            );
+            assert_eq!(
+                vec![String::from("up")],
+                dfile
+                    .sections
+                    .iter()
+                    .flat_map(|x| x.docs.clone())
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        #[test]
+        fn symbol_kinds() {
+            let cases = [
+                ("cd() {", "cd", SymbolKind::Function),
+                ("2mp4 () {", "2mp4", SymbolKind::Function),
+                (
+                    "function glsnips() {",
+                    "glsnips",
+                    SymbolKind::FunctionKeyword,
+                ),
+                ("function fbr {", "fbr", SymbolKind::FunctionKeyword),
+                ("alias docs='cd ~/Documents'", "docs", SymbolKind::Alias),
+            ];
+            for (line, name, kind) in cases.iter() {
+                assert_eq!(
+                    Some(Symbol {
+                        name: name.to_string(),
+                        kind: *kind,
+                        line: 0,
+                        value: None,
+                    }),
+                    parse_symbol(line)
+                );
+            }
+        }
+
+        #[test]
+        fn no_symbol() {
+            assert_eq!(None, parse_symbol("eval $(thefuck --alias)"));
+            assert_eq!(None, next_symbol("\n\n# a comment\ncd() {", 1));
+        }
+
+        #[test]
+        fn doc_uses_definition_name() {
+            let sample = "#;\n# chdir()\n# moves to given directory\n#\"\n\ncd() {\n";
+            let x = parse_strings_from_file(Span::new(CompleteStr(sample)), Delimiters::default())
+                .unwrap()
+                .1;
+            let doc = Doc::make_doc(&x[0], Delimiters::default()).unwrap();
+            assert_eq!("cd", doc.name);
+            assert_eq!(Some(SymbolKind::Function), doc.kind);
+            assert_eq!("chdir()", doc.short_description);
+            assert_eq!(6, x[0].definition.as_ref().unwrap().line);
+        }
+
+        #[test]
+        fn finds_undocumented() {
+            let sample = "#;\n# cd()\n# moves to given directory\n#\"\ncd() {\n}\n\nalias docs='cd ~/Documents'\nexport EDITOR='vim'\n";
+            let x = parse_strings_from_file(Span::new(CompleteStr(sample)), Delimiters::default())
+                .unwrap()
+                .1;
+            let docs = [Doc::make_doc(&x[0], Delimiters::default()).unwrap()];
+            let found = scan_definitions(sample, &docs);
+            assert_eq!(2, found.len());
+            assert!(found[0].documented);
+            assert_eq!("docs", found[1].symbol.name);
+            assert_eq!(8, found[1].symbol.line);
+            assert!(!found[1].documented);
+        }
+
+        #[test]
+        fn block_and_definition_ranges() {
+            let sample = "ls\n  #;\n  # fs()\n  # find files\n  #\"\n  fs() {\n    echo \"}\" # }\n    if [ 1 ]; then { true; }; fi\n  }\n";
+            let x = parse_strings_from_file(Span::new(CompleteStr(sample)), Delimiters::default())
+                .unwrap()
+                .1;
+            let doc = Doc::make_doc(&x[0], Delimiters::default()).unwrap();
+            let point = |line, column| Point { line, column };
+            assert_eq!(
+                Range {
+                    start: point(2, 3),
+                    end: point(5, 4)
+                },
+                doc.block_range
+            );
+            assert_eq!(
+                Some(Range {
+                    start: point(6, 3),
+                    end: point(9, 3)
+                }),
+                doc.definition_range
+            );
+        }
+    }
+
+    mod lexer_tests {
+        use super::*;
+        #[test]
+        fn classifies_lines() {
+            let sample = "\n# fs()\n# find files\n# @param dir: where to look\n# - DEPTH: how far\n# @return paths\n# @exitcode 1: none found\n";
+            let kinds: Vec<LineKind> = lex(sample, Delimiters::default())
+                .iter()
+                .map(|x| x.kind)
+                .collect();
+            assert_eq!(
+                vec![
+                    LineKind::Other,
+                    LineKind::Name,
+                    LineKind::Comment,
+                    LineKind::Param,
+                    LineKind::Descriptor,
+                    LineKind::Return,
+                    LineKind::Tag,
+                ],
+                kinds
+            );
+            let tag = lex("# @exitcode 1: none found", Delimiters::default())[0];
+            assert_eq!("@exitcode", tag.tag);
+            assert_eq!(" 1: none found", tag.value);
+        }
+
+        #[test]
+        fn tag_is_not_name() {
+            let sample = "\n# @param dir: where to look\n# fs()\n";
+            let parsed = parse_doc(sample, Delimiters::default());
+            match parsed {
+                Err(Err::Failure(Context::Code(at, ErrorKind::Custom(MISSING_NAME)))) => {
+                    assert!(at.starts_with("# @param"))
+                }
+                _ => panic!("expected a missing name"),
+            }
+        }
+    }
+
+    mod doc_tests {
+        use super::*;
+        #[test]
+        fn line_endings() {
+            let lf = "#;\n# fs()\n# find files\n# @param dir: where to look\n# @example\n# fs .\n# => ./a\n#\"\nfs() {\n}";
+            let crlf = lf.replace('\n', "\r\n");
+            let parse = |sample: &str| {
+                let x =
+                    parse_strings_from_file(Span::new(CompleteStr(sample)), Delimiters::default())
+                        .unwrap()
+                        .1;
+                Doc::make_doc(&x[0], Delimiters::default()).unwrap()
+            };
+            let (doc, windows) = (parse(lf), parse(&crlf));
+            assert_eq!(doc, windows);
+            assert_eq!("where to look", windows.params[0].value);
+            assert_eq!(Some(String::from("./a")), windows.examples[0].output);
+            assert_eq!(doc.definition_range, windows.definition_range);
+            let unterminated = parse_doc("\n# fs()\n# find files", Delimiters::default())
+                .unwrap()
+                .1;
+            assert_eq!("find files", unterminated.long_description);
+        }
+
+        #[test]
+        fn multi_paragraph_description() {
+            let sample = "
+        # fs()
+        # Fuzzy file and directory search
+        # across the current tree
+        #
+        # Opens the selection with $EDITOR
+        # @param query: initial query
+        ";
+            let doc = parse_doc(sample, Delimiters::default()).unwrap().1;
+            assert_eq!(
+                "Fuzzy file and directory search across the current tree\n\nOpens the selection with $EDITOR",
+                doc.long_description
+            );
+            assert_eq!(1, doc.params.len());
+            assert_eq!("query", doc.params[0].key);
+            assert_eq!("initial query", doc.params[0].value);
+        }
+
+        #[test]
+        fn description_stops_at_descriptor() {
+            let sample = "\n# fs()\n# Fuzzy search\n# - CTRL-O to open\n";
+            let doc = parse_doc(sample, Delimiters::default()).unwrap().1;
+            assert_eq!("Fuzzy search", doc.long_description);
+            assert_eq!(1, doc.descriptors.len());
+        }
+
+        #[test]
+        fn tags_in_any_order() {
+            let sample = "
+        # mp()
+        # Convert from markdown to docx
+        # @return void
+        # - MSG: the message to pass
+        # @param input: markdown file to convert
+        # - OUT: where to write
+        # @param style: reference docx
+        ";
+            let doc = parse_doc(sample, Delimiters::default()).unwrap().1;
+            let keys = |kvs: &[KV]| kvs.iter().map(|x| x.key.clone()).collect::<Vec<_>>();
+            let params: Vec<_> = doc.params.iter().map(|x| x.key.clone()).collect();
+            assert_eq!(vec!["input", "style"], params);
+            assert_eq!(vec!["MSG", "OUT"], keys(&doc.descriptors));
+            assert_eq!(vec!["void"], keys(&doc.returns));
+        }
+    }
+
+    mod example_tests {
+        use super::*;
+        #[test]
+        fn example_with_output() {
+            let sample = "
+# up()
+# Move up directories
+# @example climb two levels
+#   cd /tmp/a/b
+#   up 2
+#
+#   pwd
+# =>
+#   /tmp
+# @param count: levels to move
+";
+            let doc = parse_doc(sample, Delimiters::default()).unwrap().1;
+            assert_eq!(
+                vec![Example {
+                    description: String::from("climb two levels"),
+                    code: String::from("  cd /tmp/a/b\n  up 2\n\n  pwd"),
+                    output: Some(String::from("  /tmp")),
+                }],
+                doc.examples
+            );
+            assert_eq!(1, doc.params.len());
+            assert_eq!("Move up directories", doc.long_description);
+        }
+
+        #[test]
+        fn example_until_end() {
+            let sample = "\n# up()\n# Move up\n# @example\n# up\n# => /\n";
+            let doc = parse_doc(sample, Delimiters::default()).unwrap().1;
+            assert_eq!("up", doc.examples[0].code);
+            assert_eq!(Some(String::from("/")), doc.examples[0].output);
+        }
+
+        #[test]
+        fn example_keeps_tag_like_lines() {
+            let sample = "\n# ll()\n# List long\n# @example\n# ll a.txt\n# @decorated\n# =>\n# -rw-r--r-- 1 me me 0 a.txt\n# @return listing\n";
+            let doc = parse_doc(sample, Delimiters::default()).unwrap().1;
+            assert_eq!("ll a.txt\n@decorated", doc.examples[0].code);
+            assert_eq!(
+                Some(String::from("-rw-r--r-- 1 me me 0 a.txt")),
+                doc.examples[0].output
+            );
+            assert!(doc.descriptors.is_empty());
+            assert_eq!(1, doc.returns.len());
+        }
     }
 
-    pub fn to_html(docstrings: &[DocFile], dir: Option<&str>, template_loc: Option<&str>) {
-        for dfile in docstrings {
-            let json = to_json(dfile);
-            let handlebars = Handlebars::new();
-            let mut template = match template_loc {
-                Some(m) => match File::open(m) {
-                    Ok(o) => o,
-                    Err(_) => {
-                        std::dbg!("Provided path is invalid");
-                        exit(1);
-                    }
+    mod deprecation_tests {
+        use super::*;
+        #[test]
+        fn is_as_deprecation() {
+            assert_eq!(
+                Deprecation {
+                    since: Some(String::from("0.5.0")),
+                    replacement: Some(String::from("fs")),
                 },
-                None => File::open("./static/template.hbs").unwrap(),
-            };
-            // let mut template = File::open("./static/template.hbs").unwrap();
-            let mut output = match dir {
-                Some(d) if Path::new(d).is_dir() => {
-                    File::create(format!("{}/{}.html", d, dfile.filename).as_str())
-                        .expect("File could not be created")
-                }
-                None | Some(_) => {
-                    std::dbg!("Provided path is invalid");
-                    exit(1);
-                }
-            };
-            // let mut output = if dir.len() == 1 {
-            //     File::create(format!("{}.html", dfile.filename).as_str())
-            //         .expect("File cannot be created")
-            // } else {
-            //     File::create(format!("{}/{}.html", dir, dfile.filename).as_str())
-            //         .expect("File cannot be created")
-            // };
-            handlebars
-                .render_template_source_to_write(&mut template, &json, &mut output)
-                .expect("Could not generate documentation");
+                as_deprecation(" 0.5.0 use fs")
+            );
+            assert_eq!(
+                Deprecation {
+                    since: None,
+                    replacement: Some(String::from("fs")),
+                },
+                as_deprecation(" use fs")
+            );
+            assert_eq!(Deprecation::default(), as_deprecation(""));
+        }
+
+        #[test]
+        fn deprecated_doc() {
+            let sample = "\n# ff()\n# Find files\n# @deprecated 0.5.0 use fs\n";
+            let doc = parse_doc(sample, Delimiters::default()).unwrap().1;
+            assert_eq!("Find files", doc.long_description);
+            assert_eq!(
+                "deprecated since 0.5.0, use fs instead",
+                doc.deprecated.unwrap().to_string()
+            );
         }
     }
-}
 
-/// Functions and declarations for generating/overriding delimiters
-mod delims {
-    use super::*;
-    use std::io::prelude::*;
-    /// Represents the necessary delimiters for a `bashdoc`
-    #[derive(Debug, Serialize, Deserialize, Copy, Clone)]
-    pub struct Delimiters<'a> {
-        pub start: &'a str,
-        pub end: &'a str,
-        pub params: &'a str,
-        pub ret: &'a str,
-        pub opt: &'a str,
-        pub comm: &'a str,
+    mod reference_tests {
+        use super::*;
+        #[test]
+        fn is_as_references() {
+            let names: Vec<_> = as_references(" fs(), fbr")
+                .into_iter()
+                .map(|x| x.name)
+                .collect();
+            assert_eq!(vec!["fs", "fbr"], names);
+        }
+
+        #[test]
+        fn reference_line() {
+            let sample = "echo\n#;\n# fs()\n# find files\n# @see fbr\n#\"\nfs() {\n}\n";
+            let docs = get_strings(sample, Delimiters::default()).unwrap();
+            let dfile = generate_doc_file(&docs, Path::new("a/zshrc"), Delimiters::default());
+            assert_eq!("a/zshrc", dfile.path);
+            assert_eq!(5, dfile.thedocs[0].see[0].source_line);
+        }
+
+        #[test]
+        fn resolves_across_files() {
+            let doc = |name: &str, see: &str, line: u32| Doc {
+                name: String::from(name),
+                see: as_references(see),
+                definition_range: Some(Range {
+                    start: Point { line, column: 1 },
+                    end: Point { line, column: 1 },
+                }),
+                ..Default::default()
+            };
+            let mut files = vec![
+                DocFile {
+                    filename: String::from("zshrc"),
+                    path: String::from("a/zshrc"),
+                    thedocs: vec![doc("fs", " gits missing", 12)],
+                    ..Default::default()
+                },
+                DocFile {
+                    filename: String::from("zshrc"),
+                    path: String::from("b/zshrc"),
+                    thedocs: vec![doc("gits", "", 40)],
+                    ..Default::default()
+                },
+            ];
+            resolve_references(&mut files);
+            let see = &files[0].thedocs[0].see;
+            assert_eq!(Some(String::from("zshrc")), see[0].file);
+            assert_eq!(Some(String::from("b/zshrc")), see[0].path);
+            assert_eq!(Some(40), see[0].line);
+            assert_eq!(None, see[1].file);
+        }
     }
 
-    impl<'a> Default for Delimiters<'a> {
-        fn default() -> Delimiters<'a> {
-            Delimiters {
-                start: "#;",
-                end: "#\"",
-                params: "@param",
-                ret: "@return",
-                opt: "# -",
-                comm: "# ",
-            }
+    mod exitcode_tests {
+        use super::*;
+        #[test]
+        fn is_as_exitcode() {
+            assert_eq!(
+                Ok(ExitCode {
+                    code: 1,
+                    meaning: String::from("no match found")
+                }),
+                as_exitcode(" 1: no match found")
+            );
+            assert_eq!(
+                Ok(ExitCode {
+                    code: 0,
+                    meaning: String::from("success")
+                }),
+                as_exitcode(" 0 success")
+            );
+            assert!(as_exitcode(" one: no match found").is_err());
+            assert!(as_exitcode(" 256: out of range").is_err());
+        }
+
+        #[test]
+        fn outputs_and_codes() {
+            let sample = "
+# fs()
+# Fuzzy file search
+# @stdout the selected path
+# @exitcode 0: a file was selected
+# @stderr fzf errors
+# @exitcode 130: cancelled
+";
+            let doc = parse_doc(sample, Delimiters::default()).unwrap().1;
+            assert_eq!(vec!["the selected path"], doc.stdout);
+            assert_eq!(vec!["fzf errors"], doc.stderr);
+            let codes: Vec<u8> = doc.exit_codes.iter().map(|x| x.code).collect();
+            assert_eq!(vec![0, 130], codes);
+            assert!(doc.returns.is_empty());
         }
     }
-    impl<'a> Delimiters<'a> {
-        /// Override default delimiters with passed in values
-        pub fn override_delims(overrides: &'a ArgMatches<'a>) -> Self {
-            let mut result: Delimiters = Delimiters::default();
-            for key in overrides.args.keys() {
-                match key.as_ref() {
-                    "start" => result.start = overrides.value_of(key).unwrap(),
-                    "end" => result.end = overrides.value_of(key).unwrap(),
-                    "descriptor" => result.opt = overrides.value_of(key).unwrap(),
-                    "params" => result.params = overrides.value_of(key).unwrap(),
-                    "returns" => result.ret = overrides.value_of(key).unwrap(),
-                    "comment" => result.comm = overrides.value_of(key).unwrap(),
-                    _ => {}
-                }
-            }
-            result
+
+    mod env_tests {
+        use super::*;
+        #[test]
+        fn env_report() {
+            let sample = "
+# fs()
+# Fuzzy file search
+# @env $EDITOR: editor to open files with
+# @env RIPGREP_CONFIG_PATH: rg settings
+";
+            let mut doc = parse_doc(sample, Delimiters::default()).unwrap().1;
+            assert_eq!(
+                vec![
+                    KV::new(
+                        String::from("EDITOR"),
+                        String::from("editor to open files with")
+                    ),
+                    KV::new(
+                        String::from("RIPGREP_CONFIG_PATH"),
+                        String::from("rg settings")
+                    ),
+                ],
+                doc.env
+            );
+            doc.name = String::from("fs");
+            let mut other = doc.clone();
+            other.name = String::from("xi");
+            other.env.truncate(1);
+            let files = [DocFile {
+                filename: String::from("zshrc"),
+                thedocs: vec![doc, other],
+                ..Default::default()
+            }];
+            let report = env_usage(&files);
+            assert_eq!(
+                Some(&vec![
+                    String::from("fs (zshrc)"),
+                    String::from("xi (zshrc)")
+                ]),
+                report.get("EDITOR")
+            );
+            assert_eq!(1, report["RIPGREP_CONFIG_PATH"].len());
         }
+    }
 
-        /// Read/Write contents of `$BASHDOC_CONFIG_PATH` for use as Delimiters.
-        pub fn get_delims() -> Self {
-            let mut contents = String::new();
-            if env::current_dir().unwrap().join(".bashdocrc").is_file() {
-                let mut config =
-                    File::open(Path::new(&env::current_dir().unwrap().join(".bashdocrc")))
-                        .expect("Invalid path");
-                config
-                    .read_to_string(&mut contents)
-                    .expect("could not read from file.");
-                let mut to_convert = String::new();
-                to_convert.push_str(&contents);
-                let as_static: &'static str = Box::leak(to_convert.into_boxed_str());
-                let sorted: Delimiters = toml::from_str(as_static).unwrap();
-                sorted
-            } else {
-                match env::var_os("BASHDOC_CONFIG_PATH") {
-                    Some(val) => {
-                        let mut config = File::open(Path::new(&val)).expect("Invalid path");
-                        config
-                            .read_to_string(&mut contents)
-                            .expect("could not read from file.");
-                        let mut to_convert = String::new();
-                        to_convert.push_str(&contents);
-                        let as_static: &'static str = Box::leak(to_convert.into_boxed_str());
-                        let sorted: Delimiters = toml::from_str(as_static).unwrap();
-                        sorted
-                    }
-                    None => {
-                        let delimiters = Delimiters::default();
-                        let content = toml::to_string_pretty(&delimiters)
-                            .expect("Could not be converted to TOML");
-                        let mut path = home_dir().unwrap();
-                        path.push(".bashdocrc");
-                        fs::write(path.to_str().unwrap(), content).unwrap();
-                        env::set_var("BASHDOC_CONFIG_PATH", path);
-                        delimiters
-                    }
-                }
-            }
+    mod custom_tag_tests {
+        use super::*;
+        #[test]
+        fn single_and_list_tags() {
+            let tags = [
+                CustomTag {
+                    name: String::from("owner"),
+                    kind: TagKind::Single,
+                },
+                CustomTag {
+                    name: String::from("requires"),
+                    kind: TagKind::List,
+                },
+            ];
+            let delims = Delimiters {
+                tags: &tags,
+                ..Default::default()
+            };
+            let sample = "
+# fs()
+# Fuzzy file search
+# @owner dustin
+# @requires fzf, rg
+# @requires bat
+# @unknown ignored
+";
+            let doc = parse_doc(sample, delims).unwrap().1;
+            assert_eq!(2, doc.tags.len());
+            assert_eq!(
+                Some(&TagValue::Single(String::from("dustin"))),
+                doc.tags.get("owner")
+            );
+            assert_eq!(
+                Some(&TagValue::List(vec![
+                    String::from("fzf"),
+                    String::from("rg"),
+                    String::from("bat")
+                ])),
+                doc.tags.get("requires")
+            );
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    mod kv_tests {
+    mod diagnostic_tests {
         use super::*;
+        fn diagnose(sample: &str) -> Diagnostic {
+            let x = parse_strings_from_file(Span::new(CompleteStr(sample)), Delimiters::default())
+                .unwrap()
+                .1;
+            Doc::make_doc(&x[0], Delimiters::default()).unwrap_err()
+        }
+
         #[test]
-        fn new_kv() {
-            let kv = KV::new(String::from("a"), String::from("b"));
-            assert_eq!(String::from("a"), kv.key);
-            assert_eq!(String::from("b"), kv.value);
+        fn empty_block_reported_once() {
+            let sample = "#;\n#\n#\"\n";
+            let dfile =
+                parse_source(sample, Path::new("empty.sh"), Delimiters::default(), true).unwrap();
+            assert_eq!(1, dfile.diagnostics.len());
+            assert_eq!(
+                expected(&ErrorKind::Custom(EMPTY_BLOCK)),
+                dfile.diagnostics[0].expected
+            );
+            let unknown = "echo\n# @todo tidy up\n# @nolint\n# @see fs\n";
+            assert_eq!(1, lint(unknown, Delimiters::default()).len());
         }
 
         #[test]
-        fn cmp_kv() {
-            let kv1 = KV::new(String::from("a"), String::from("b"));
-            let kv2 = KV::new(String::from("a"), String::from("b"));
-            let kv = KV::new(String::from("b"), String::from("a"));
-            assert_eq!(kv1, kv2);
-            assert_ne!(kv1, kv);
+        fn bad_exitcode() {
+            let diagnostic = diagnose(
+                "cd() {\n}\n#;\n# fs()\n# Find files\n# @exitcode one: no match\n#\"\nfs() {\n",
+            );
+            assert_eq!(6, diagnostic.line);
+            assert_eq!(13, diagnostic.column);
+            assert_eq!(3, diagnostic.width);
+            assert_eq!("an exit status from 0 to 255", diagnostic.expected);
+            assert_eq!(
+                "error: expected an exit status from 0 to 255\n --> :6:13\n  |\n6 | # @exitcode one: no match\n  |             ^^^",
+                diagnostic.to_string()
+            );
         }
 
         #[test]
-        fn is_as_kv() {
-            let conv = as_kv("type: mp4 or gif");
+        fn crlf_snippet() {
+            let diagnostic = diagnose("#;\r\n# fs()\r\n# @exitcode x\r\n#\"\r\n");
+            assert_eq!("# @exitcode x", diagnostic.snippet);
+            assert_eq!(3, diagnostic.line);
+        }
+
+        #[test]
+        fn first_line_snippet() {
+            let diagnostic = diagnose("  #;@exitcode x\n#\"\n");
+            assert_eq!("  #;@exitcode x", diagnostic.snippet);
+            assert_eq!((1, 5), (diagnostic.line, diagnostic.column));
+        }
+
+        #[test]
+        fn missing_name() {
+            let diagnostic = diagnose("#;\n\n#\"\n");
+            assert_eq!(1, diagnostic.line);
+            assert_eq!("a name line after the start delimiter", diagnostic.expected);
+        }
+
+        #[test]
+        fn empty_param() {
+            let diagnostic = diagnose("#;\n# fs()\n# Find\n# @param\n#\"\n");
+            assert_eq!(4, diagnostic.line);
+            assert_eq!(9, diagnostic.column);
+            assert_eq!("a parameter name after the tag", diagnostic.expected);
+        }
+
+        #[test]
+        fn lint_blocks() {
+            let sample =
+                "#!/bin/bash\n# @param x: stray\n#;\n# ok()\n#\"\n#;\n#\n#\"\n#;\n# unclosed()\n";
+            let found: Vec<(u32, String)> = lint(sample, Delimiters::default())
+                .into_iter()
+                .map(|x| (x.line, x.expected))
+                .collect();
             assert_eq!(
-                KV {
-                    key: String::from("type"),
-                    value: String::from("mp4 or gif")
-                },
-                conv.unwrap()
+                vec![
+                    (2, String::from("this tag to be inside a docstring")),
+                    (6, String::from("a name line inside this docstring")),
+                    (9, String::from("an end delimiter to close this docstring")),
+                ],
+                found
             );
         }
+    }
 
+    mod encoding_tests {
+        use super::*;
         #[test]
-        fn is_as_kv_white() {
-            let conv = as_kv("CTRL-O to open with `open` command,");
+        fn decodes() {
             assert_eq!(
-                KV {
-                    key: String::from("CTRL-O"),
-                    value: String::from("to open with `open` command,")
-                },
-                conv.unwrap()
+                (String::from("caf\u{FFFD}"), true),
+                Encoding::Utf8.decode(b"caf\xe9")
+            );
+            assert_eq!(
+                (String::from("caf\u{e9}"), false),
+                Encoding::Latin1.decode(b"caf\xe9")
+            );
+            assert_eq!(
+                (String::from("\u{201C}hi\u{201D}"), false),
+                Encoding::Windows1252.decode(b"\x93hi\x94")
+            );
+            let utf16: Vec<u8> = "# fs()".encode_utf16().flat_map(u16::to_le_bytes).collect();
+            assert_eq!(
+                (String::from("# fs()"), false),
+                Encoding::Utf16Le.decode(&utf16)
+            );
+            assert_eq!(Ok(Encoding::Windows1252), "CP1252".parse());
+            assert!("ebcdic".parse::<Encoding>().is_err());
+        }
+
+        #[test]
+        fn binary_files() {
+            assert!(is_binary(b"\x7fELF\x02\x01\x01\x00", Encoding::Utf8));
+            assert!(!is_binary(b"#;\n# fs()\n#\"\n", Encoding::Utf8));
+            assert!(!is_binary(&[0x23, 0x00], Encoding::Utf16Le));
+            assert_eq!(
+                Some(Encoding::Utf16Le),
+                Encoding::from_bom(&[0xFF, 0xFE, 0x23, 0x00])
             );
         }
     }
 
     mod docfile_tests {
         use super::*;
+        #[test]
+        fn start_with_glob() {
+            let dir = env::temp_dir().join(format!("bashdoc-glob-{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(
+                dir.join("a.zsh"),
+                "#;\n# up()\n# go up\n# @see build\n#\"\nup() {\n}\n",
+            )
+            .unwrap();
+            fs::write(dir.join("b.lua"), "--;\n-- greet()\n-- say hello\n--\"\n").unwrap();
+            fs::write(
+                dir.join("Makefile"),
+                "#;\n# build\n# make it\n# @deprecated 2.0\n#\"\nbuild:\n",
+            )
+            .unwrap();
+            let languages = BTreeMap::new();
+            let pick = |p: &Path| detect_delims(p, &languages, Delimiters::default());
+            let pattern = format!("{}/*", dir.display());
+            let mut files = start(
+                Cow::from(pattern.clone()),
+                pick,
+                Encoding::default(),
+                true,
+                false,
+            )
+            .unwrap();
+            let hidden = start(Cow::from(pattern), pick, Encoding::default(), false, true).unwrap();
+            fs::remove_dir_all(&dir).unwrap();
+            files.sort_by(|a, b| a.filename.cmp(&b.filename));
+            let names: Vec<_> = files
+                .iter()
+                .map(|x| (x.filename.as_str(), x.thedocs[0].name.as_str()))
+                .collect();
+            assert_eq!(
+                vec![("Makefile", "build"), ("a", "up"), ("b", "greet")],
+                names
+            );
+            assert!(files.iter().all(|x| x.diagnostics.is_empty()));
+            assert_eq!(
+                Some(String::from("Makefile")),
+                files[1].thedocs[0].see[0].file
+            );
+            let up = hidden.iter().find(|x| x.filename == "a").unwrap();
+            assert_eq!(None, up.thedocs[0].see[0].file);
+            assert!(hidden
+                .iter()
+                .all(|x| x.thedocs.iter().all(|doc| doc.name != "build")));
+        }
+
+        #[test]
+        fn header_from_file_tag() {
+            let sample = "#;\n# @file Shell helpers\n# Aliases and functions for every shell.\n# @author Jane Doe\n# @author John Doe\n# @license MIT\n#\"\n#;\n# up()\n# go up\n#\"\nup() { cd ..; }\n";
+            let docs = get_strings(sample, Delimiters::default()).unwrap();
+            let dfile = generate_doc_file(&docs, Path::new("helpers.sh"), Delimiters::default());
+            assert_eq!(
+                Some(FileHeader {
+                    summary: String::from("Shell helpers"),
+                    description: String::from("Aliases and functions for every shell."),
+                    author: Some(String::from("Jane Doe, John Doe")),
+                    license: Some(String::from("MIT")),
+                }),
+                dfile.header
+            );
+            assert_eq!(1, dfile.thedocs.len());
+            assert_eq!("up", dfile.thedocs[0].name);
+        }
+
+        #[test]
+        fn header_from_leading_comment() {
+            let sample = "#!/bin/bash\n# Shell helpers\n#\n# Aliases and functions.\n# @license MIT\n\n#;\n# up()\n# go up\n#\"\n";
+            let header = leading_comment(sample, Delimiters::default())
+                .map(|x| as_header(x, Delimiters::default()))
+                .unwrap();
+            assert_eq!("Shell helpers", header.summary);
+            assert_eq!("Aliases and functions.", header.description);
+            assert_eq!(Some(String::from("MIT")), header.license);
+            assert!(lint(sample, Delimiters::default()).is_empty());
+            let attached = "# goes up\nup() { cd ..; }\n";
+            assert_eq!(None, leading_comment(attached, Delimiters::default()));
+            let heading = "# EXPORTS\nexport A=1\n";
+            assert_eq!(None, leading_comment(heading, Delimiters::default()));
+        }
+
+        #[test]
+        fn sections_from_headings() {
+            let sample = "# EXPORTS\nexport A=1\n\n# ALIASES\n# Shortcut to ~/Documents\n#;\n# docs()\n# go to documents\n#\"\n#;\n# dl()\n# go to downloads\n#\"\n# METHODS\n# ZSH_THEME=\"refined\"\n#;\n# fs()\n# find files\n#\"\n";
+            let docs = get_strings(sample, Delimiters::default()).unwrap();
+            let dfile = generate_doc_file(&docs, Path::new("zshrc"), Delimiters::default());
+            assert_eq!(
+                vec![
+                    Section {
+                        name: Some(String::from("ALIASES")),
+                        docs: vec![String::from("docs"), String::from("dl")],
+                    },
+                    Section {
+                        name: Some(String::from("METHODS")),
+                        docs: vec![String::from("fs")],
+                    },
+                ],
+                dfile.sections
+            );
+            let delims = Delimiters {
+                section: "## ",
+                ..Default::default()
+            };
+            assert_eq!(Some("Git helpers"), as_heading("## Git helpers", delims));
+            let body = "#;\n# a()\n# first\n#\"\na() {\n    # TODO\n    echo \"{\"\n}\n#;\n# b()\n# second\n#\"\nb() {\n}\n";
+            let docs = get_strings(body, Delimiters::default()).unwrap();
+            let dfile = generate_doc_file(&docs, Path::new("body.sh"), Delimiters::default());
+            assert_eq!(
+                vec![Section {
+                    name: None,
+                    docs: vec![String::from("a"), String::from("b")],
+                }],
+                dfile.sections
+            );
+            assert_eq!(None, as_heading("# Git helpers", Delimiters::default()));
+        }
+
         #[test]
         fn test_add() {
             let mut dfile = DocFile {
                 thedocs: Vec::new(),
+                variables: Vec::new(),
                 filename: String::from("zshrc"),
+                path: String::from("zshrc"),
+                header: None,
+                undocumented: Vec::new(),
+                sections: Vec::new(),
+                diagnostics: Vec::new(),
             };
             dfile.add(Doc {
+                name: String::from("lala"),
+                kind: None,
+                value: None,
                 short_description: String::from("lala"),
                 long_description: String::from("rawr"),
                 descriptors: Vec::new(),
                 params: Vec::new(),
                 returns: Vec::new(),
+                examples: Vec::new(),
+                deprecated: None,
+                see: Vec::new(),
+                env: Vec::new(),
+                exit_codes: Vec::new(),
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+                tags: BTreeMap::new(),
+                section: None,
                 position: 0,
+                block_range: Range::default(),
+                definition_range: None,
             });
             assert_eq!(
                 dfile.thedocs,
                 [Doc {
+                    name: String::from("lala"),
+                    kind: None,
+                    value: None,
                     short_description: String::from("lala"),
                     long_description: String::from("rawr"),
                     descriptors: Vec::new(),
                     params: Vec::new(),
                     returns: Vec::new(),
+                    examples: Vec::new(),
+                    deprecated: None,
+                    see: Vec::new(),
+                    env: Vec::new(),
+                    exit_codes: Vec::new(),
+                    stdout: Vec::new(),
+                    stderr: Vec::new(),
+                    tags: BTreeMap::new(),
+                    section: None,
                     position: 0,
+                    block_range: Range::default(),
+                    definition_range: None,
                 }]
             );
         }
     }
 
+    mod delims_tests {
+        use super::*;
+        #[test]
+        fn presets() {
+            for lang in LANGUAGES {
+                assert!(Delimiters::preset(lang).is_some());
+            }
+            assert_eq!(None, Delimiters::preset("cobol").map(|x| x.start));
+            let lua = Delimiters::preset("lua").unwrap();
+            assert_eq!(("--;", "-- "), (lua.start, lua.comm));
+        }
+
+        #[test]
+        fn config_lang() {
+            let config: Config = toml::from_str("lang = \"c\"\nparams = \"@arg\"\n").unwrap();
+            let delims = config.delims();
+            assert_eq!("//;", delims.start);
+            assert_eq!("@arg", delims.params);
+            assert_eq!("@return", delims.ret);
+        }
+
+        #[test]
+        fn profiles_and_rules() {
+            let config: Config = toml::from_str(
+                "profile = \"shdoc\"\n[profiles.shdoc]\nstart = \"##\"\n[profiles.lua]\nlang = \"lua\"\n[[rules]]\nglob = \"legacy/*.sh\"\nprofile = \"lua\"\n",
+            )
+            .unwrap();
+            assert_eq!("##", config.delims().start);
+            assert_eq!("--;", config.profile(Some("lua")).delims().start);
+            assert_eq!("--;", config.delims_for(Path::new("legacy/a.sh")).start);
+            assert_eq!("##", config.delims_for(Path::new("a.sh")).start);
+            assert_eq!("//;", config.delims_for(Path::new("a.c")).start);
+        }
+
+        #[test]
+        fn modeline() {
+            let sample =
+                "#!/bin/bash\n# bashdoc: start=\"##\" end=\"##.\"\n##\n# fs()\n# find files\n##.\n";
+            let delims = with_modeline(sample, Delimiters::default());
+            assert_eq!(("##", "##.", "# "), (delims.start, delims.end, delims.comm));
+            let input = Span::new(CompleteStr(sample)).slice(skip_modeline(sample)..);
+            let x = parse_strings_from_file(input, delims).unwrap().1;
+            assert_eq!(1, x.len());
+            assert_eq!(3, x[0].block.start.line);
+            assert!(lint(sample, delims).is_empty());
+            let lua = with_modeline(
+                "-- bashdoc: lang=\"lua\" opt=\"-- *\"\n",
+                Delimiters::default(),
+            );
+            assert_eq!(("--;", "-- *"), (lua.start, lua.opt));
+            let late = "\n\n\n\n\n# bashdoc: start=\"##\"\n";
+            assert_eq!("#;", with_modeline(late, Delimiters::default()).start);
+            assert_eq!(
+                "#;",
+                with_modeline("echo bashdoc: start=\"##\"", Delimiters::default()).start
+            );
+            let prose = "#;\n# bashdoc: wrapper around the tool\n#\"\nregen() {\n}\n";
+            assert_eq!(0, skip_modeline(prose));
+            assert_eq!("#;", with_modeline(prose, Delimiters::default()).start);
+            let empty = "# bashdoc: start=\"\" end=\"\"\n#;\n# up()\n#\"\n";
+            assert_eq!(0, skip_modeline(empty));
+            assert_eq!("#;", with_modeline(empty, Delimiters::default()).start);
+        }
+
+        #[test]
+        fn modeline_applies_to_docs() {
+            let sample = "# bashdoc: start=\"##\" end=\"##.\" params=\"@arg\"\n##\n# greet()\n# say hello\n# @arg name: who\n##.\ngreet() {\n}\n";
+            let dfile =
+                parse_source(sample, Path::new("greet.sh"), Delimiters::default(), true).unwrap();
+            assert!(dfile.diagnostics.is_empty());
+            assert_eq!(1, dfile.thedocs.len());
+            assert_eq!("name", dfile.thedocs[0].params[0].key);
+            let lua = "-- bashdoc: lang=\"lua\"\n--;\n-- up()\n-- go up\n--\"\n";
+            let dfile =
+                parse_source(lua, Path::new("notes.txt"), Delimiters::default(), false).unwrap();
+            assert!(dfile.diagnostics.is_empty());
+            assert_eq!("up", dfile.thedocs[0].name);
+        }
+
+        #[test]
+        fn detects_lang() {
+            let none = BTreeMap::new();
+            let detect = |p: &str, languages| detect_lang(Path::new(p), languages);
+            assert_eq!(
+                Some(String::from("makefile")),
+                detect("build/Makefile", &none)
+            );
+            assert_eq!(
+                Some(String::from("fish")),
+                detect("conf.d/prompt.fish", &none)
+            );
+            assert_eq!(Some(String::from("zsh")), detect("example/zshrc", &none));
+            assert_eq!(None, detect("notes.txt", &none));
+            let mut languages = BTreeMap::new();
+            languages.insert(String::from("bats"), String::from("bash"));
+            languages.insert(String::from("prompt.fish"), String::from("lua"));
+            assert_eq!(Some(String::from("bash")), detect("test.bats", &languages));
+            assert_eq!(
+                Some(String::from("lua")),
+                detect("conf.d/prompt.fish", &languages)
+            );
+        }
+
+        #[test]
+        fn detects_shebang() {
+            assert_eq!(Some("bash"), lang_for_shebang("#!/usr/bin/env bash\n"));
+            assert_eq!(Some("python"), lang_for_shebang("#!/usr/bin/python3.8"));
+            assert_eq!(Some("makefile"), lang_for_shebang("#!/usr/bin/make -f"));
+            assert_eq!(None, lang_for_shebang("# not a shebang"));
+        }
+
+        #[test]
+        fn keeps_configured_delims() {
+            let none = BTreeMap::new();
+            let custom = Delimiters {
+                start: "##",
+                ..Default::default()
+            };
+            assert_eq!("##", detect_delims(Path::new("a.zsh"), &none, custom).start);
+            assert_eq!(
+                "--;",
+                detect_delims(Path::new("a.lua"), &none, custom).start
+            );
+        }
+    }
+
     #[test]
     fn param_and_input() {
         let sample = "#\"
@@ -662,10 +3878,15 @@ mod tests {
         # - MSG: the message to pass
         #;
         ";
-        let delims = Delimiters::get_delims();
+        let config = Config::get_config();
+        let delims = config.delims();
         let x = Extracted {
             content: sample.into(),
-            position: Span::new(CompleteStr(sample)),
+            prefix: String::new(),
+            definition: None,
+            block: Range::default(),
+            definition_range: None,
+            section: None,
         };
 
         let val = generate_doc_file(&[x], Path::new("/example.txt"), delims);
@@ -26,6 +26,41 @@
 //!
 //!with lots of color!
 //!
+//!The name shown for each entry is taken from the function, alias or `export` defined directly below the docstring. When there is none, the first line of the docstring is used.
+//!
+//!A docstring above an `export`, `readonly`, `declare` or plain `NAME=value` line documents a variable. Variables are listed after the functions and aliases, with their kind and value, and under `variables` in JSON.
+//!
+//!Every comment line between the name line and the first `@param`, `@return` or descriptor makes up the long description. A blank comment line (`#`) starts a new paragraph.
+//!
+//!Params may give a type, a default, and be marked optional or variadic, e.g. `@param {int} count=5 [optional]: how many` or `@param files...`.
+//!
+//!An `@example` line starts a usage example. The comment lines after it, up to the next tag, are kept verbatim. Lines after a `# =>` line are the expected output.
+//!
+//!Mark retired helpers with `@deprecated [since] [use other_fn]`. Pass `--hide-deprecated` to leave them out.
+//!
+//!`@see other_fn` links to another documented name in any of the files given. A warning is printed for names that are not found.
+//!
+//!`@env NAME: description` documents an environment variable a function reads or sets. `--env-report` lists each variable and the functions that use it.
+//!
+//!`@exitcode N: meaning` documents an exit status from 0 to 255. `@stdout` and `@stderr` describe what is printed to each stream.
+//!
+//!A docstring that cannot be parsed is left out, and an error giving its file, line and column is printed to stderr. With `--strict`, unterminated or empty docstrings and tags written outside of one are reported too, and bashdoc exits with an error if anything was reported.
+//!
+//!Files are read as UTF-8 unless they start with a byte order mark, or `--encoding` names `latin1`, `windows-1252`, `utf-16le` or `utf-16be`. Text that is not valid in the encoding is replaced, with a warning, and binary files are skipped.
+//!
+//!A docstring tagged `@file` describes the file itself, with `@author` and `@license` lines. Without one, a comment at the top of the file that is followed by a blank line is used. Its first paragraph, or the `@file` text, is the summary shown beside the file name in the terminal and HTML, and it is listed under `header` in JSON.
+//!
+//!```bash
+//!#!/bin/bash
+//!# Helpers for moving around
+//!# @author Jane Doe
+//!# @license MIT
+//!```
+//!
+//!A comment heading such as `# ALIASES` starts a section, and the docs below it are grouped under that name in the terminal, JSON `sections` and the HTML sidebar. A heading written with the comment delimiter must be in capitals. A different `section` delimiter, e.g. `section = "## "` in `.bashdocrc`, `override --section` or the modeline, accepts any text after it.
+//!
+//!In JSON output each doc has a `block_range` for its docstring and a `definition_range` for the function, alias or export it documents. Both hold the `start` and `end` line and column, and a function's range runs to its closing brace.
+//!
 //!### Global Delimiters
 //!
 //! The default delimiters to use are as follows:
@@ -44,6 +79,50 @@
 //!
 //!These can be modifed in your `.bashdocrc`.
 //!
+//!Built-in presets can be chosen with `--lang` or a `lang` key in `.bashdocrc`, and any delimiters set alongside `lang` replace those of the preset. `bash`, `zsh`, `sh`, `fish`, `powershell`, `makefile`, `dockerfile` and `python` use the defaults above. `c` uses `//;`, `//"`, `// -` and `// `, and `lua` uses `--;`, `--"`, `-- -` and `-- `.
+//!
+//!`INPUT` can be a file, a directory, whose files are all read, or a quoted glob pattern such as `"~/dotfiles/*"`.
+//!
+//!Unless `--lang` or `override` is given, each file's language is also detected from its name, its extension or its `#!` line. A file whose language comments differently from your `.bashdocrc` delimiters uses that language's preset instead. A `[languages]` table maps file names or extensions to a language, and it takes priority over detection.
+//!
+//!```toml
+//![languages]
+//!bats = "bash"
+//!Justfile = "makefile"
+//!```
+//!
+//!Named profiles hold their own delimiters, and they can be chosen with `--profile`. A top-level `profile` key picks the default profile. `[[rules]]` entries choose a profile for files matching a glob, and the first matching rule applies.
+//!
+//!```toml
+//!profile = "shdoc"
+//!
+//![profiles.shdoc]
+//!lang = "bash"
+//!
+//![profiles.legacy]
+//!start = "##"
+//!end = "##."
+//!
+//![[rules]]
+//!glob = "legacy/*.sh"
+//!profile = "legacy"
+//!```
+//!
+//!A file can set its own delimiters with a `bashdoc:` modeline in its first five lines. The modeline takes `lang`, `start`, `end`, `params`, `ret`, `opt`, `comm` and `section`, and it applies on top of whatever delimiters the file would otherwise use.
+//!
+//!```bash
+//!#!/bin/bash
+//!# bashdoc: start="##" end="##."
+//!```
+//!
+//!Extra tags can be declared in a `[tags]` table, each either `"single"` or `"list"`. Their values are listed under `tags` in JSON and templates.
+//!
+//!```toml
+//![tags]
+//!owner = "single"
+//!requires = "list"
+//!```
+//!
 //!## Install
 //!
 //! ```bash
@@ -73,12 +152,19 @@
 //!
 //!FLAGS:
 //!-c, --color      toggles color
+//!    --env-report           list every environment variable named by @env and the functions that use it
 //!-h, --help       Prints help information
+//!    --hide-deprecated      leave out anything marked @deprecated
+//!    --show-undocumented    list functions and aliases that have no documentation
+//!    --strict               exit with an error on malformed docstrings
 //!-V, --version    Prints version information
 //!-w, --watch      continuously update on change
 //!
 //!OPTIONS:
 //!-j, --json <FILE>            print result as JSON
+//!    --encoding <encoding>    encoding of the input files, utf-8 unless they start with a byte order mark
+//!    --lang <lang>            use the built-in delimiters for a language
+//!    --profile <profile>      use a profile from .bashdocrc
 //!-l, --location <location>    location to save HTML
 //!-t, --template <template>    .hbs template to use for generation of documentation
 //!